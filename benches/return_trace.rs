@@ -0,0 +1,94 @@
+#![feature(test)]
+
+extern crate test;
+
+use std::num::ParseIntError;
+
+use test::{black_box, Bencher};
+use trial_and_error::return_trace::age::set_hop_timing;
+use trial_and_error::return_trace::memory::set_memory_accounting;
+use trial_and_error::return_trace::unhandled::set_unhandled_detection;
+use trial_and_error::return_trace::{Ok, Result, TracedError};
+
+const ITERATIONS: u32 = 1_000;
+
+fn std_add(value: u32) -> std::result::Result<u32, ParseIntError> {
+    std::result::Result::Ok(black_box(value) + 1)
+}
+
+fn std_sum() -> std::result::Result<u32, ParseIntError> {
+    let mut total = 0;
+
+    for value in 0..ITERATIONS {
+        total = std_add(value)?;
+    }
+
+    std::result::Result::Ok(total)
+}
+
+fn traced_add(value: u32) -> Result<u32, TracedError<ParseIntError>> {
+    Ok(black_box(value) + 1)
+}
+
+fn traced_sum() -> Result<u32, TracedError<ParseIntError>> {
+    let mut total = 0;
+
+    for value in 0..ITERATIONS {
+        total = traced_add(value)?;
+    }
+
+    Ok(total)
+}
+
+fn traced_parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = input.parse::<u32>()?;
+    Ok(value)
+}
+
+fn traced_nested(input: &str, depth: usize) -> Result<u32, TracedError<ParseIntError>> {
+    if depth == 0 {
+        return traced_parse(input);
+    }
+
+    let value = traced_nested(input, depth - 1)?;
+    Ok(value)
+}
+
+#[bench]
+fn happy_path_std_result(b: &mut Bencher) {
+    b.iter(|| black_box(std_sum()));
+}
+
+#[bench]
+fn happy_path_traced_result(b: &mut Bencher) {
+    b.iter(|| black_box(traced_sum().is_ok()));
+}
+
+#[bench]
+fn error_path_traced_result_depth_16(b: &mut Bencher) {
+    b.iter(|| black_box(traced_nested(black_box("nope"), 16).is_err()));
+}
+
+/// Turn every setting that adds work to recording a frame on or off.
+fn set_push_settings(enabled: bool) {
+    set_hop_timing(enabled);
+    set_memory_accounting(enabled);
+    set_unhandled_detection(enabled);
+}
+
+#[bench]
+fn error_path_traced_result_depth_16_settings_off(b: &mut Bencher) {
+    set_push_settings(false);
+    b.iter(|| black_box(traced_nested(black_box("nope"), 16).is_err()));
+}
+
+#[bench]
+fn error_path_traced_result_depth_16_settings_on(b: &mut Bencher) {
+    set_push_settings(true);
+    b.iter(|| {
+        let error = traced_nested(black_box("nope"), 16).unwrap_err();
+        error.mark_handled();
+        black_box(error)
+    });
+    set_push_settings(false);
+}
@@ -0,0 +1,299 @@
+//! Pretty-print traced errors encoded with `return_trace::binary`.
+//!
+//! Reads an encoded trace from a file, or from stdin if no file is given, and prints it with its
+//! frames colored by kind and, where the source files can be read from the current directory, the
+//! line each frame points at. The input can be the raw encoding, or the same bytes written out as
+//! hex, as they usually are when harvested from logs.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read, Write as _};
+use std::process;
+
+use trial_and_error::return_trace::binary::{self, DecodedFrame, DecodedTrace};
+use trial_and_error::return_trace::FrameKind;
+
+const USAGE: &str = "\
+Usage: trace-view [OPTIONS] [FILE]
+
+Pretty-print a traced error encoded with `return_trace::binary`, read from FILE or stdin.
+
+Options:
+    --filter <PATTERN>   Only show frames whose path contains PATTERN
+    --exclude <PATTERN>  Hide frames whose path contains PATTERN
+    --no-snippets        Don't show the source line of each frame
+    --no-color           Don't color the output
+    -h, --help           Print this message
+";
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const DIM: &str = "\x1b[2m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// What to print, as given on the command line.
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    file: Option<String>,
+    filters: Vec<String>,
+    excludes: Vec<String>,
+    snippets: bool,
+    color: bool,
+}
+
+impl Options {
+    fn parse<I>(args: I) -> Result<Options, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options {
+            snippets: true,
+            color: true,
+            ..Options::default()
+        };
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--filter" | "--exclude" => {
+                    let pattern = args
+                        .next()
+                        .ok_or_else(|| format!("`{}` needs a pattern", arg))?;
+
+                    if arg == "--filter" {
+                        options.filters.push(pattern);
+                    } else {
+                        options.excludes.push(pattern);
+                    }
+                }
+                "--no-snippets" => options.snippets = false,
+                "--no-color" => options.color = false,
+                "-h" | "--help" => return Err(String::new()),
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option `{}`", arg))
+                }
+                _ if options.file.is_some() => return Err("only one file can be given".into()),
+                _ => options.file = Some(arg),
+            }
+        }
+
+        Ok(options)
+    }
+
+    /// Whether `frame` should be shown.
+    fn shows(&self, frame: &DecodedFrame) -> bool {
+        let matches = |pattern: &String| frame.file().contains(pattern.as_str());
+
+        (self.filters.is_empty() || self.filters.iter().any(matches))
+            && !self.excludes.iter().any(matches)
+    }
+
+    /// `text` wrapped in `color`, if colors are enabled.
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Decode `input`, which is either the raw encoding or the encoding as hex digits.
+fn decode(input: &[u8]) -> Result<DecodedTrace, String> {
+    if let Ok(trace) = binary::decode(input) {
+        return Ok(trace);
+    }
+
+    let digits: Vec<u8> = input
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    let not_a_trace = || "input is not an encoded trace".to_string();
+
+    if digits.len() % 2 != 0 {
+        return Err(not_a_trace());
+    }
+
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| not_a_trace())?;
+            u8::from_str_radix(pair, 16).map_err(|_| not_a_trace())
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    binary::decode(&bytes).map_err(|_| not_a_trace())
+}
+
+/// Render `trace` according to `options`.
+fn render(trace: &DecodedTrace, options: &Options) -> String {
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "{} {}",
+        options.paint(&format!("{}{}", BOLD, RED), "error:"),
+        trace.message()
+    );
+
+    for (key, value) in trace.metadata() {
+        let _ = writeln!(
+            out,
+            "  {} {}",
+            options.paint(DIM, &format!("{}:", key)),
+            value
+        );
+    }
+
+    if !trace.sources().is_empty() {
+        let _ = writeln!(out, "\nCaused by:");
+        for (index, source) in trace.sources().iter().enumerate() {
+            let _ = writeln!(out, "{:>5}: {}", index, source);
+        }
+    }
+
+    let shown: Vec<_> = trace
+        .frames()
+        .iter()
+        .enumerate()
+        .filter(|(_, frame)| options.shows(frame))
+        .collect();
+
+    if !trace.frames().is_empty() {
+        let _ = writeln!(out, "\nError return trace:");
+    }
+
+    for (index, frame) in &shown {
+        if let Some(elision) = trace.elision().filter(|elision| elision.index() == *index) {
+            let elided = format!("      ... {} frames elided ...", elision.count());
+            let _ = writeln!(out, "{}", options.paint(DIM, &elided));
+        }
+
+        let color = match frame.kind() {
+            FrameKind::Propagated => BLUE,
+            FrameKind::Converted | FrameKind::Sent => YELLOW,
+        };
+        let _ = writeln!(
+            out,
+            "{:>4}: {}",
+            trace.original_index(*index),
+            options.paint(color, &frame.to_string())
+        );
+
+        if options.snippets {
+            if let Some(line) = source_line(frame) {
+                let _ = writeln!(out, "      {}", options.paint(DIM, line.trim()));
+            }
+        }
+    }
+
+    let hidden = trace.frames().len() - shown.len();
+    if hidden > 0 {
+        let _ = writeln!(
+            out,
+            "{}",
+            options.paint(DIM, &format!("      ({} frames hidden)", hidden))
+        );
+    }
+
+    out
+}
+
+/// The line of source `frame` points at, if its file can be read.
+fn source_line(frame: &DecodedFrame) -> Option<String> {
+    let source = fs::read_to_string(frame.file()).ok()?;
+    let index = (frame.line() as usize).checked_sub(1)?;
+
+    source.lines().nth(index).map(str::to_string)
+}
+
+fn run() -> Result<(), String> {
+    let options = Options::parse(env::args().skip(1))?;
+
+    let input = match options.file.as_deref() {
+        Some(path) if path != "-" => {
+            fs::read(path).map_err(|e| format!("could not read `{}`: {}", path, e))?
+        }
+        _ => {
+            let mut input = Vec::new();
+            io::stdin()
+                .read_to_end(&mut input)
+                .map_err(|e| format!("could not read stdin: {}", e))?;
+            input
+        }
+    };
+
+    let trace = decode(&input)?;
+    let _ = io::stdout().write_all(render(&trace, &options).as_bytes());
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(message) = run() {
+        if message.is_empty() {
+            print!("{}", USAGE);
+        } else {
+            eprintln!("trace-view: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use trial_and_error::return_trace::{Ok as TracedOk, Result as TracedResult, TracedError};
+
+    fn parse(input: &str) -> TracedResult<u32, TracedError<std::num::ParseIntError>> {
+        let value = input.parse::<u32>()?;
+        TracedOk(value)
+    }
+
+    fn args(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = args(&["--filter", "src/", "--no-color", "trace.bin"]).unwrap();
+
+        assert_eq!(vec!["src/".to_string()], options.filters);
+        assert_eq!(Some("trace.bin".to_string()), options.file);
+        assert!(!options.color);
+        assert!(options.snippets);
+
+        assert!(args(&["--filter"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+        assert!(args(&["a", "b"]).is_err());
+    }
+
+    #[test]
+    fn decodes_raw_and_hex_input() {
+        let bytes = binary::encode(&parse("x").unwrap_err());
+        let hex: String = bytes.iter().map(|b| format!("{:02x}\n", b)).collect();
+
+        let raw = decode(&bytes).unwrap();
+        assert_eq!(raw, decode(hex.as_bytes()).unwrap());
+        assert!(decode(b"nope").is_err());
+    }
+
+    #[test]
+    fn renders_snippets_and_hides_filtered_frames() {
+        let trace = decode(&binary::encode(&parse("x").unwrap_err())).unwrap();
+
+        let mut options = args(&["--no-color"]).unwrap();
+        let rendered = render(&trace, &options);
+        assert!(rendered.starts_with("error: invalid digit found in string\n"));
+        assert!(rendered.contains("\n      let value = input.parse::<u32>()?;\n"));
+
+        options.excludes.push("trace-view".to_string());
+        let rendered = render(&trace, &options);
+        assert!(rendered.ends_with("      (1 frames hidden)\n"));
+    }
+}
@@ -1,10 +1,15 @@
 //! An experimental crate for proposals from the error handling project group.
 //!
-//! This crate currently contains two experiments, each in their own module.
+//! This crate currently contains three experiments, each in their own module.
 //!
 //! 1. An alternative to `Box<dyn Error + ...>` that implements `Error`.
 //! 2. An error reporter that wraps an error and handles iterating over sources
 //!    and formatting a full error report.
+//! 3. A `Result` type whose `?` operator records error return traces.
+//!
+//! Applications adopting traced results can bring everything they need into scope with
+//! `use trial_and_error::prelude::*`, and set up everything else, like the panic hook and
+//! hyperlinks, with a call to `trial_and_error::install()` at the start of `main`.
 //!
 #![feature(try_trait_v2)]
 #![feature(termination_trait_lib)]
@@ -14,6 +19,9 @@
 #![feature(exhaustive_patterns)]
 #![feature(backtrace)]
 #![feature(error_iter)]
+#![feature(min_specialization)]
+#![feature(rustc_attrs)]
+#![feature(once_cell)]
 
 #![warn(
     missing_docs,
@@ -29,6 +37,10 @@
 
 pub mod boxerror_replacement;
 pub mod error_reporter;
+pub mod prelude;
+pub mod return_trace;
 
 pub use boxerror_replacement::{DynError, DynResult};
 pub use error_reporter::Report;
+pub use return_trace::setup::install;
+pub use return_trace::{MaybeTraced, Traced, TracedError, TracedReport};
@@ -0,0 +1,31 @@
+//! Convenience re-exports for applications using traced results.
+//!
+//! A single glob import brings the traced `Result`, its variants, and the tracing types into
+//! scope. Within the prelude, `Result` defaults its error type to `TracedReport`, so most
+//! functions only need to name their success type.
+//!
+//! ```rust
+//! use trial_and_error::prelude::*;
+//!
+//! fn parse(input: &str) -> Result<u32> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! fn main() -> Result<()> {
+//!     let value = parse("4")?;
+//!
+//!     assert_eq!(4, value);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+pub use crate::return_trace::{
+    Err, IntoTraced, MaybeTraced, Ok, OptionExt, ResultExt, Traced, TracedError, TracedReport,
+    TryFromTraced, TryIntoTraced,
+};
+
+/// `Result` type that records error return traces, defaulting to `TracedReport` errors.
+pub type Result<T, E = TracedReport> = crate::return_trace::Result<T, E>;
@@ -0,0 +1,1954 @@
+//! Experimental error return traces.
+//!
+//! A `Backtrace` captured when an error is created shows how the program got to the point where
+//! the error occurred, but it says nothing about the path the error took afterwards as it was
+//! propagated back up the stack with `?`. When errors are handled far away from where they were
+//! created, this propagation path is often the more interesting one.
+//!
+//! This module defines a `Result` type that mirrors `std::result::Result`, but whose `?` operator
+//! is `#[track_caller]`. Every time an error is propagated with `?` inside a function returning
+//! this `Result`, the location of the `?` is handed to the error if it implements the `Traced`
+//! trait. The `TracedError` wrapper implements `Traced` for any error type by collecting these
+//! locations into a `TraceLog`.
+//!
+//! Errors that don't implement `Traced` are propagated exactly like they are with
+//! `std::result::Result`; the decision of whether to record a location is made with
+//! specialization, so no bounds are required on the error type. With the `dyn-dispatch` feature
+//! enabled, the decision is made at runtime through the `MaybeTraced` trait instead, which error
+//! types then need to implement.
+//!
+//! Traced results can also be consumed with `?` by callers still returning
+//! `std::result::Result`. The location of that `?` is recorded before the error is converted, and
+//! the trace is handed to the hook registered with `set_boundary_hook`, if any, so it isn't lost
+//! when the caller's error type has nowhere to keep it.
+//!
+//! Errors from other crates can start a trace without a `?` with `IntoTraced`, `ResultExt`,
+//! `OptionExt`, `TryIntoTraced` and `TryFromTraced`. `TracedReport` is a type erased traced error,
+//! similar to `Box<dyn Error + Send + Sync>`, that keeps the trace of any traced error converted
+//! into it. What `?` records can be narrowed with `set_frame_denylist` and `set_trace_mode`.
+//!
+//! Everything else lives in submodules:
+//!
+//! - Setting up: [`setup`] configures an application in one call, and [`hyperlink`] links printed
+//!   frames to their source.
+//! - Recording more: [`capture`](mod@capture), [`deep_trace`](mod@deep_trace), [`fields`],
+//!   [`age`], [`traceparent`], [`channel`], and [`location`] for positions in sources other than
+//!   Rust.
+//! - Other kinds of errors: [`context`], [`borrowed`], [`panic`](mod@panic), and [`poison`].
+//! - Storing traces: [`storage`], [`static_buffer`], [`interner`], and [`memory`].
+//! - Reading traces: [`visit`], [`diagnostic`], [`markdown`], [`timeline`], and
+//!   [`assert`](mod@assert).
+//! - Handling errors in bulk: [`stats`], [`fingerprint`], [`rate_limit`], [`sink`], and
+//!   [`unhandled`].
+//! - Exporting traces: [`binary`], [`chrome_trace`], and [`response`].
+//! - Behind features: `futures`, `html`, `instrumented`, `parallel` with `rayon`, `stable` with
+//!   `stable-dispatch`, and `tokio`. The `unstable-result` feature adds the methods that are still
+//!   unstable on `std::result::Result`, like `into_ok`.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     // `?` works directly on `std::result::Result`, starting the trace here
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! fn double(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = parse(input)?;
+//!
+//!     Ok(value * 2)
+//! }
+//!
+//! let error = double("four").unwrap_err();
+//!
+//! assert_eq!(2, error.trace_log().len());
+//! ```
+
+use std::backtrace::Backtrace;
+use std::convert::{Infallible, TryFrom, TryInto};
+use std::error::Error;
+use std::fmt;
+use std::lazy::SyncLazy;
+use std::ops::{ControlFlow, FromResidual, Try};
+use std::panic::Location;
+use std::process::Termination;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+
+pub use self::Result::{Err, Ok};
+
+pub mod age;
+pub mod assert;
+pub mod binary;
+pub mod borrowed;
+pub mod capture;
+pub mod channel;
+pub mod chrome_trace;
+pub mod context;
+pub mod deep_trace;
+pub mod diagnostic;
+pub mod fields;
+pub mod fingerprint;
+#[cfg(feature = "futures")]
+pub mod futures;
+#[cfg(feature = "html")]
+pub mod html;
+pub mod hyperlink;
+#[cfg(feature = "instrumented")]
+pub mod instrumented;
+pub mod interner;
+pub mod location;
+pub mod markdown;
+pub mod memory;
+pub mod panic;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod poison;
+pub mod rate_limit;
+pub mod response;
+pub mod setup;
+pub mod sink;
+#[cfg(feature = "stable-dispatch")]
+pub mod stable;
+pub mod static_buffer;
+pub mod stats;
+pub mod storage;
+pub mod timeline;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+pub mod traceparent;
+pub mod unhandled;
+pub mod visit;
+
+use self::age::HopTime;
+use self::capture::Capture;
+use self::deep_trace::DeepFrame;
+use self::fields::Fields;
+#[cfg(feature = "instrumented")]
+use self::instrumented::MaybeInstrument;
+use self::location::LocationLike;
+use self::storage::TraceStorage;
+use self::traceparent::TraceParent;
+
+/// `Result` type that records the location of each `?` it is propagated through.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Debug, Hash)]
+#[must_use = "this `Result` may be an `Err` variant, which should be handled"]
+pub enum Result<T, E> {
+    /// Contains the success value.
+    Ok(T),
+    /// Contains the error value.
+    Err(E),
+}
+
+/// An error type that can record the locations it is propagated through.
+///
+/// Propagation specializes on this trait, so crates implementing it need to enable
+/// `#![feature(min_specialization)]`, unless the `dyn-dispatch` feature is enabled.
+#[cfg_attr(not(feature = "dyn-dispatch"), rustc_specialization_trait)]
+pub trait Traced {
+    /// Record that the error was propagated through `location`.
+    fn trace(&mut self, location: &'static Location<'static>);
+
+    /// The locations recorded so far, if the error keeps them in a `TraceLog`.
+    fn as_trace_log(&self) -> Option<&TraceLog> {
+        None
+    }
+
+    /// The position of the error in a distributed trace, if one was attached.
+    fn trace_parent(&self) -> Option<&TraceParent> {
+        None
+    }
+
+    /// The fields set for the whole error, if it keeps any.
+    fn fields(&self) -> Option<&Fields> {
+        None
+    }
+
+    /// Attach the value of a local variable to the next frame recorded; see `capture!`.
+    ///
+    /// Errors that can't keep the value drop it.
+    fn capture(&mut self, _name: &'static str, _value: String) {}
+
+    /// Capture a full backtrace and attach it to the next frame recorded; see `deep_trace!`.
+    ///
+    /// Errors that can't keep the backtrace don't capture one.
+    fn capture_backtrace(&mut self) {}
+}
+
+/// An error type that is guaranteed to keep the locations it is propagated through.
+///
+/// Every `Traced` type is a `TracedMarker`; the trait only exists so `TracedResult` can check that
+/// its error type is traced.
+pub trait TracedMarker: Traced {
+    /// The error type itself.
+    type Error;
+}
+
+impl<E> TracedMarker for E
+where
+    E: Traced,
+{
+    type Error = E;
+}
+
+/// A traced `Result` whose error type is checked to be `Traced` at compile time.
+///
+/// Unlike `Result<T, E>`, which happily holds errors that are never traced, naming a
+/// `TracedResult` with an untraced error type doesn't compile, which lets library authors promise
+/// in their signatures that the errors they return always carry a trace.
+///
+/// ```rust
+/// use std::num::ParseIntError;
+/// use trial_and_error::return_trace::{Ok, TracedError, TracedResult};
+///
+/// fn parse(input: &str) -> TracedResult<u32, TracedError<ParseIntError>> {
+///     let value = input.parse::<u32>()?;
+///
+///     Ok(value)
+/// }
+///
+/// assert_eq!(1, parse("four").unwrap_err().trace_log().len());
+/// ```
+///
+/// ```rust,compile_fail
+/// use std::num::ParseIntError;
+/// use trial_and_error::return_trace::{Ok, TracedResult};
+///
+/// fn parse(input: &str) -> TracedResult<u32, ParseIntError> {
+///     let value = input.parse::<u32>()?;
+///
+///     Ok(value)
+/// }
+/// ```
+// Type aliases don't check their bounds, but projecting through `TracedMarker` does
+pub type TracedResult<T, E> = Result<T, <E as TracedMarker>::Error>;
+
+/// An error type that may or may not implement `Traced`, decided at runtime.
+///
+/// By default every type implements this trait through specialization, so it never needs to be
+/// named. With the `dyn-dispatch` feature enabled, specialization isn't used to find out whether an
+/// error is `Traced`; instead, errors propagated through a traced `Result` must implement this
+/// trait, and recording a location costs a virtual call. Types implementing `Traced` get an
+/// implementation for free, as do the error types from `std`; any other error type opts in with
+/// an empty `impl MaybeTraced for MyError {}`.
+pub trait MaybeTraced {
+    /// The error as a `Traced` trait object, if it implements `Traced`.
+    fn as_traced_mut(&mut self) -> Option<&mut dyn Traced> {
+        None
+    }
+
+    /// The error as a shared `Traced` trait object, if it implements `Traced`.
+    fn as_traced(&self) -> Option<&dyn Traced> {
+        None
+    }
+}
+
+#[cfg(not(feature = "dyn-dispatch"))]
+impl<E> MaybeTraced for E {
+    default fn as_traced_mut(&mut self) -> Option<&mut dyn Traced> {
+        None
+    }
+
+    default fn as_traced(&self) -> Option<&dyn Traced> {
+        None
+    }
+}
+
+impl<E> MaybeTraced for E
+where
+    E: Traced,
+{
+    fn as_traced_mut(&mut self) -> Option<&mut dyn Traced> {
+        Some(self)
+    }
+
+    fn as_traced(&self) -> Option<&dyn Traced> {
+        Some(self)
+    }
+}
+
+/// Implement `MaybeTraced` for error types that never implement `Traced`.
+#[cfg(feature = "dyn-dispatch")]
+macro_rules! impl_untraced {
+    ($($ty:ty),* $(,)?) => {
+        $(impl MaybeTraced for $ty {})*
+    };
+}
+
+#[cfg(feature = "dyn-dispatch")]
+impl MaybeTraced for &str {}
+
+#[cfg(feature = "dyn-dispatch")]
+impl<B> MaybeTraced for std::borrow::Cow<'_, B> where B: ToOwned + ?Sized {}
+
+#[cfg(feature = "dyn-dispatch")]
+impl_untraced!(
+    (),
+    String,
+    Infallible,
+    BoxError,
+    Box<dyn Error + 'static>,
+    crate::DynError,
+    fmt::Error,
+    std::io::Error,
+    std::num::ParseIntError,
+    std::num::ParseFloatError,
+    std::num::TryFromIntError,
+    std::str::ParseBoolError,
+    std::str::Utf8Error,
+    std::string::FromUtf8Error,
+    std::char::ParseCharError,
+    std::net::AddrParseError,
+    std::time::SystemTimeError,
+    setup::AlreadyInstalled,
+);
+
+/// The trace an error has collected so far, if it keeps one.
+fn trace_log_of<E>(error: &E) -> Option<&TraceLog>
+where
+    E: MaybeTraced + ?Sized,
+{
+    error.as_traced().and_then(Traced::as_trace_log)
+}
+
+/// Hook called with the trace of every traced error that crosses into a `std::result::Result`.
+type BoundaryHook = Box<dyn Fn(&TraceLog) + Send + Sync + 'static>;
+
+static BOUNDARY_HOOK: SyncLazy<RwLock<Option<BoundaryHook>>> = SyncLazy::new(|| RwLock::new(None));
+
+/// Register a hook that is handed the trace of every traced error propagated with `?` into a
+/// function returning `std::result::Result`, replacing any previously registered hook.
+pub fn set_boundary_hook(hook: BoundaryHook) {
+    *BOUNDARY_HOOK.write().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+}
+
+/// Unregister the current boundary hook, returning it.
+pub fn take_boundary_hook() -> Option<BoundaryHook> {
+    BOUNDARY_HOOK
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+}
+
+/// Hand the trace of an error leaving the traced world to the boundary hook.
+fn flush_to_boundary_hook<E>(error: &E)
+where
+    E: MaybeTraced,
+{
+    if let Some(trace) = trace_log_of(error) {
+        call_boundary_hook(trace);
+    }
+}
+
+/// Hand `trace` to the boundary hook, if one is registered.
+fn call_boundary_hook(trace: &TraceLog) {
+    let hook = BOUNDARY_HOOK.read().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(hook) = hook.as_ref() {
+        trace.mark_handled();
+        hook(trace);
+    }
+}
+
+/// Set of patterns matched against the file path of each frame before it is recorded.
+#[derive(Debug, Clone, Default)]
+struct FrameDenylist {
+    /// Patterns where `*` matches any sequence of characters, including `/`.
+    patterns: Vec<String>,
+}
+
+impl FrameDenylist {
+    /// Whether frames at `location` should be dropped.
+    fn denies<L>(&self, location: &L) -> bool
+    where
+        L: LocationLike + ?Sized,
+    {
+        let path = location.file();
+
+        self.patterns
+            .iter()
+            .any(|pattern| matches_pattern(pattern, path))
+    }
+}
+
+/// Match `path` against a pattern where `*` matches any sequence of characters.
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one item
+    let first = parts.next().unwrap_or_default();
+
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut parts: Vec<_> = parts.collect();
+    let last = match parts.pop() {
+        Some(last) => last,
+        // A pattern without any `*` has to match the whole path
+        None => return rest.is_empty(),
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(ind) => rest = &rest[ind + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+// Checked before taking the lock so that the common case of no denylist stays cheap
+static DENYLIST_ENABLED: AtomicBool = AtomicBool::new(false);
+
+static FRAME_DENYLIST: SyncLazy<RwLock<FrameDenylist>> =
+    SyncLazy::new(|| RwLock::new(FrameDenylist::default()));
+
+/// Drop frames whose file path matches any of `patterns` at record time, replacing any
+/// previously set patterns.
+///
+/// Patterns are matched against the whole of `Location::file`, where `*` matches any sequence of
+/// characters, e.g. `*/generated/*` or `*/.cargo/registry/*`.
+pub fn set_frame_denylist<I, S>(patterns: I)
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let patterns: Vec<String> = patterns.into_iter().map(Into::into).collect();
+    let enabled = !patterns.is_empty();
+
+    FRAME_DENYLIST
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .patterns = patterns;
+    DENYLIST_ENABLED.store(enabled, Ordering::Release);
+}
+
+/// Stop dropping frames at record time.
+pub fn clear_frame_denylist() {
+    set_frame_denylist(Vec::<String>::new());
+}
+
+/// Whether frames at `location` are dropped by the frame denylist.
+fn is_denylisted<L>(location: &L) -> bool
+where
+    L: LocationLike + ?Sized,
+{
+    DENYLIST_ENABLED.load(Ordering::Acquire)
+        && FRAME_DENYLIST
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .denies(location)
+}
+
+// The settings that add work to recording a frame, as one word, so that recording a frame with
+// all of them off only loads it once and takes a single branch
+static PUSH_FLAGS: AtomicU8 = AtomicU8::new(0);
+
+/// Frames are timed; see the `age` module.
+const HOP_TIMING: u8 = 1 << 0;
+/// Traces are watched for being dropped unhandled; see the `unhandled` module.
+const UNHANDLED_DETECTION: u8 = 1 << 1;
+/// Traces add what they hold to the memory tally; see the `memory` module.
+const MEMORY_ACCOUNTING: u8 = 1 << 2;
+
+/// Turn one of the settings that add work to recording a frame on or off.
+fn set_push_flag(flag: u8, enabled: bool) {
+    if enabled {
+        PUSH_FLAGS.fetch_or(flag, Ordering::Relaxed);
+    } else {
+        PUSH_FLAGS.fetch_and(!flag, Ordering::Relaxed);
+    }
+}
+
+/// The settings that add work to recording a frame that are on.
+#[inline]
+fn push_flags() -> u8 {
+    PUSH_FLAGS.load(Ordering::Relaxed)
+}
+
+/// What propagating an error with `?` records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceMode {
+    /// Record each location in the trace of errors that implement `Traced`.
+    Full,
+    /// Only bump a global per-location counter, leaving errors untouched.
+    Counters,
+}
+
+static TRACE_MODE: AtomicU8 = AtomicU8::new(TraceMode::Full as u8);
+
+/// Change what propagating an error with `?` records, for every thread.
+pub fn set_trace_mode(mode: TraceMode) {
+    TRACE_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+/// What propagating an error with `?` currently records.
+pub fn trace_mode() -> TraceMode {
+    match TRACE_MODE.load(Ordering::Relaxed) {
+        mode if mode == TraceMode::Counters as u8 => TraceMode::Counters,
+        _ => TraceMode::Full,
+    }
+}
+
+/// Record that an already traced error changed type at `location`, unless tracing is disabled for
+/// that location.
+fn record_conversion<S>(trace: &mut S, location: &'static Location<'static>)
+where
+    S: TraceStorage,
+{
+    if !is_denylisted(location) && trace_mode() == TraceMode::Full {
+        trace.push_frame(TraceFrame::converted(location));
+    }
+}
+
+/// Record `location` for the error according to the trace mode, unless it is denylisted.
+fn record<E>(error: &mut E, location: &'static Location<'static>)
+where
+    E: MaybeTraced + ?Sized,
+{
+    if is_denylisted(location) {
+        return;
+    }
+
+    match trace_mode() {
+        TraceMode::Full => {
+            if let Some(error) = error.as_traced_mut() {
+                error.trace(location);
+            }
+        }
+        TraceMode::Counters => stats::increment(location),
+    }
+}
+
+/// Convert an error and record the location of the `?` that propagated it.
+#[inline]
+#[track_caller]
+fn propagate<E, F>(error: E) -> F
+where
+    F: From<E> + MaybeTraced,
+{
+    let mut error = F::from(error);
+    record(&mut error, Location::caller());
+    error
+}
+
+/// What happened to an error at a `TraceFrame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrameKind {
+    /// The error was propagated with `?`.
+    Propagated,
+    /// The error was converted into a different error type, keeping its trace.
+    Converted,
+    /// The error was sent across a channel to another thread.
+    Sent,
+}
+
+/// A source location, stored compactly.
+///
+/// Unlike `Location`, which keeps a pointer to its file path, a `CompactLocation` refers to its
+/// path with an index into a global table of interned paths, which makes frames 12 bytes instead
+/// of 16 on 64-bit targets. Columns past `u16::MAX` are clamped to it.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CompactLocation {
+    /// The index of the file path in the interner.
+    file: u32,
+    /// The line number.
+    line: u32,
+    /// The column number.
+    column: u16,
+}
+
+impl CompactLocation {
+    /// The name of the source file.
+    pub fn file(&self) -> &'static str {
+        interner::resolve(self.file)
+    }
+
+    /// The line number.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The column number.
+    pub fn column(&self) -> u32 {
+        u32::from(self.column)
+    }
+}
+
+impl From<&'static Location<'static>> for CompactLocation {
+    fn from(location: &'static Location<'static>) -> Self {
+        CompactLocation {
+            file: interner::intern(location.file()),
+            line: location.line(),
+            column: u16::try_from(location.column()).unwrap_or(u16::MAX),
+        }
+    }
+}
+
+impl PartialEq<&Location<'_>> for CompactLocation {
+    fn eq(&self, other: &&Location<'_>) -> bool {
+        self.file() == other.file() && self.line == other.line() && self.column() == other.column()
+    }
+}
+
+impl fmt::Display for CompactLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file(), self.line, self.column)
+    }
+}
+
+impl fmt::Debug for CompactLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompactLocation")
+            .field("file", &self.file())
+            .field("line", &self.line)
+            .field("column", &self.column)
+            .finish()
+    }
+}
+
+/// A single location an error was propagated through.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TraceFrame {
+    // The fields of the location are inlined so the kind fits in their padding
+    /// The index of the file path of the `?` that propagated the error in the interner.
+    file: u32,
+    /// The line of the `?` that propagated the error.
+    line: u32,
+    /// The column of the `?` that propagated the error, or for `Sent` frames the index of the
+    /// label of the thread that sent it.
+    column: u16,
+    /// What happened to the error at this location.
+    kind: FrameKind,
+}
+
+impl TraceFrame {
+    /// Create a new `TraceFrame` for an error propagated through the given location.
+    pub fn new(location: &'static Location<'static>) -> Self {
+        TraceFrame::with_kind(location, FrameKind::Propagated)
+    }
+
+    /// Create a new `TraceFrame` for an error converted into a new type at the given location.
+    pub fn converted(location: &'static Location<'static>) -> Self {
+        TraceFrame::with_kind(location, FrameKind::Converted)
+    }
+
+    /// Create a new `TraceFrame` for an error sent across a channel by the current thread at the
+    /// given location.
+    pub fn sent(location: &'static Location<'static>) -> Self {
+        TraceFrame {
+            column: channel::current_thread_label(),
+            ..TraceFrame::with_kind(location, FrameKind::Sent)
+        }
+    }
+
+    fn with_kind(location: &'static Location<'static>, kind: FrameKind) -> Self {
+        let CompactLocation { file, line, column } = CompactLocation::from(location);
+
+        TraceFrame {
+            file,
+            line,
+            column,
+            kind,
+        }
+    }
+
+    /// The location of the `?` that propagated the error.
+    ///
+    /// The column of `Sent` frames isn't kept, and is reported as 0.
+    pub fn location(&self) -> CompactLocation {
+        let column = match self.kind {
+            FrameKind::Sent => 0,
+            _ => self.column,
+        };
+
+        CompactLocation {
+            file: self.file,
+            line: self.line,
+            column,
+        }
+    }
+
+    /// What happened to the error at this location.
+    pub fn kind(&self) -> FrameKind {
+        self.kind
+    }
+
+    /// The name of the thread that sent the error across a channel, or its id if it has no name,
+    /// for `Sent` frames.
+    pub fn thread(&self) -> Option<String> {
+        match self.kind {
+            FrameKind::Sent => Some(channel::thread_label(self.column)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceFrame")
+            .field("location", &self.location())
+            .field("kind", &self.kind)
+            .finish()
+    }
+}
+
+impl fmt::Display for TraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = self.location();
+
+        match self.kind {
+            FrameKind::Propagated => fmt::Display::fmt(&location, f),
+            FrameKind::Converted => write!(f, "{} (converted here)", location),
+            FrameKind::Sent => write!(
+                f,
+                "{}:{} (sent across channel by thread {})",
+                location.file(),
+                location.line(),
+                channel::thread_label(self.column)
+            ),
+        }
+    }
+}
+
+/// The ordered list of locations an error was propagated through, oldest first.
+///
+/// The frames are shared between clones of a `TraceLog`, so cloning one, or an error that holds
+/// one, is cheap. Recording a frame into a clone copies the frames first, leaving the other clones
+/// as they were.
+#[derive(Default)]
+pub struct TraceLog {
+    /// The recorded frames.
+    frames: Arc<Vec<TraceFrame>>,
+    /// The values attached to the frames with `capture!`, if any.
+    captures: Option<Arc<Vec<Capture>>>,
+    /// The backtraces attached to the frames with `deep_trace!`, if any.
+    deep_frames: Option<Arc<Vec<DeepFrame>>>,
+    /// When the frames were recorded, if hop timing was on; see the `age` module.
+    hop_times: Option<Arc<Vec<HopTime>>>,
+    /// Whether the trace warns if it's dropped unhandled; see the `unhandled` module.
+    watch: AtomicU8,
+    /// The bytes this trace added to the memory tally; see the `memory` module.
+    accounted: usize,
+}
+
+impl TraceLog {
+    /// Create a new, empty `TraceLog`.
+    pub fn new() -> Self {
+        TraceLog::default()
+    }
+
+    /// Record a new propagation location at the end of the log.
+    #[inline]
+    pub fn push(&mut self, location: &'static Location<'static>) {
+        self.push_frame(TraceFrame::new(location));
+    }
+
+    /// Record that the error was converted into a new type at the end of the log.
+    pub fn push_converted(&mut self, location: &'static Location<'static>) {
+        self.push_frame(TraceFrame::converted(location));
+    }
+
+    /// The recorded frames, oldest first.
+    pub fn frames(&self) -> &[TraceFrame] {
+        &self.frames
+    }
+
+    /// Iterate over the recorded frames, oldest first.
+    pub fn iter(&self) -> std::slice::Iter<'_, TraceFrame> {
+        self.frames.iter()
+    }
+
+    /// The number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frames have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl fmt::Debug for TraceLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.mark_handled();
+        f.debug_struct("TraceLog")
+            .field("frames", &self.frames)
+            .field("captures", &self.captures)
+            .field("deep_frames", &self.deep_frames)
+            .field("hop_times", &self.hop_times)
+            .finish()
+    }
+}
+
+impl Clone for TraceLog {
+    fn clone(&self) -> Self {
+        let mut clone = TraceLog {
+            frames: Arc::clone(&self.frames),
+            captures: self.captures.clone(),
+            deep_frames: self.deep_frames.clone(),
+            hop_times: self.hop_times.clone(),
+            watch: unhandled::unwatched(),
+            accounted: 0,
+        };
+        clone.account();
+        clone
+    }
+}
+
+impl PartialEq for TraceLog {
+    fn eq(&self, other: &Self) -> bool {
+        self.frames == other.frames
+            && self.captures == other.captures
+            && self.deep_frames == other.deep_frames
+    }
+}
+
+impl Eq for TraceLog {}
+
+impl Drop for TraceLog {
+    fn drop(&mut self) {
+        self.check_handled();
+        self.unaccount();
+    }
+}
+
+impl<'a> IntoIterator for &'a TraceLog {
+    type Item = &'a TraceFrame;
+    type IntoIter = std::slice::Iter<'a, TraceFrame>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Display for TraceLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.mark_handled();
+        for (ind, frame) in self.frames.iter().enumerate() {
+            if ind > 0 {
+                writeln!(f)?;
+            }
+
+            write!(f, "{}: ", setup::dim(format_args!("{:>4}", ind)))?;
+            hyperlink::write_linked(f, frame.location(), frame)?;
+
+            self.fmt_attached(f, ind)?;
+        }
+
+        // Values captured for a frame that was never recorded
+        self.fmt_attached(f, self.frames.len())
+    }
+}
+
+impl TraceLog {
+    /// Write the values and backtrace attached to the `index`th frame, indented below it.
+    fn fmt_attached(&self, f: &mut fmt::Formatter<'_>, index: usize) -> fmt::Result {
+        for capture in self.captures_of(index) {
+            write!(f, "\n        {} = {}", capture.name(), capture.value())?;
+        }
+
+        if let Some(backtrace) = self.backtrace_of(index) {
+            write!(f, "\n        backtrace:")?;
+            for line in backtrace.to_string().lines() {
+                write!(f, "\n          {}", line)?;
+            }
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+/// Wrapper that adds an error return trace to any error type.
+///
+/// The frames of the trace are kept in a `TraceLog` by default; see the `storage` module for
+/// other choices.
+#[derive(Debug, Clone)]
+pub struct TracedError<E, S = TraceLog> {
+    /// The wrapped error.
+    error: E,
+    /// The locations the error was propagated through.
+    trace: S,
+    /// The position of the error in a distributed trace.
+    parent: Option<Box<TraceParent>>,
+    /// The fields set for the whole error.
+    fields: Option<Box<Fields>>,
+}
+
+impl<E> TracedError<E> {
+    /// Create a new `TracedError` with an empty trace from an input error.
+    pub fn new(error: E) -> Self {
+        TracedError {
+            error,
+            trace: TraceLog::new(),
+            parent: None,
+            fields: None,
+        }
+    }
+
+    /// The locations the error was propagated through.
+    pub fn trace_log(&self) -> &TraceLog {
+        &self.trace
+    }
+}
+
+impl<E, S> TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    /// Create a new `TracedError` with an empty trace in the chosen storage from an input error.
+    pub fn with_storage(error: E) -> Self {
+        TracedError {
+            error,
+            trace: S::empty(),
+            parent: None,
+            fields: None,
+        }
+    }
+
+    /// A reference to the wrapped error.
+    pub fn inner(&self) -> &E {
+        &self.error
+    }
+
+    /// Unwrap the `TracedError`, discarding its trace.
+    pub fn into_inner(self) -> E {
+        self.mark_handled();
+        self.error
+    }
+
+    /// The storage holding the locations the error was propagated through.
+    pub fn storage(&self) -> &S {
+        &self.trace
+    }
+
+    /// Convert the wrapped error into a new type, keeping the trace and recording the call site
+    /// as a "converted here" frame.
+    #[track_caller]
+    pub fn convert<F>(self) -> TracedError<F, S>
+    where
+        F: From<E>,
+    {
+        let mut trace = self.trace;
+        record_conversion(&mut trace, Location::caller());
+
+        TracedError {
+            error: F::from(self.error),
+            trace,
+            parent: self.parent,
+            fields: self.fields,
+        }
+    }
+}
+
+/// Extension trait for wrapping any error in a `TracedError`.
+pub trait IntoTraced: Sized {
+    /// Wrap the error in a `TracedError`, recording the call site as the first frame.
+    fn into_traced(self) -> TracedError<Self>;
+}
+
+impl<E> IntoTraced for E
+where
+    E: Error,
+{
+    #[track_caller]
+    fn into_traced(self) -> TracedError<Self> {
+        let mut error = TracedError::new(self);
+        record(&mut error, Location::caller());
+        error
+    }
+}
+
+/// Extension trait for converting a `std::result::Result` into a traced `Result`.
+pub trait ResultExt<T, E> {
+    /// Convert into a traced `Result`, wrapping any error in a `TracedError` and recording the
+    /// call site as the first frame.
+    fn traced(self) -> Result<T, TracedError<E>>;
+}
+
+impl<T, E> ResultExt<T, E> for std::result::Result<T, E> {
+    #[track_caller]
+    fn traced(self) -> Result<T, TracedError<E>> {
+        match self {
+            std::result::Result::Ok(value) => Ok(value),
+            std::result::Result::Err(error) => {
+                let mut error = TracedError::new(error);
+                record(&mut error, Location::caller());
+                Err(error)
+            }
+        }
+    }
+}
+
+/// Extension trait for fallible conversions that produce traced errors.
+pub trait TryIntoTraced: Sized {
+    /// Convert into `T` with `TryInto`, wrapping the error in a `TracedError` and recording the
+    /// call site as the first frame if the conversion fails.
+    fn try_into_traced<T>(self) -> Result<T, TracedError<<Self as TryInto<T>>::Error>>
+    where
+        Self: TryInto<T>;
+}
+
+impl<V> TryIntoTraced for V {
+    #[track_caller]
+    fn try_into_traced<T>(self) -> Result<T, TracedError<<Self as TryInto<T>>::Error>>
+    where
+        Self: TryInto<T>,
+    {
+        self.try_into().traced()
+    }
+}
+
+/// Extension trait for `TryFrom` conversions that produce traced errors.
+pub trait TryFromTraced<V>: TryFrom<V> {
+    /// Convert from `value` with `TryFrom`, wrapping the error in a `TracedError` and recording
+    /// the call site as the first frame if the conversion fails.
+    fn try_from_traced(value: V) -> Result<Self, TracedError<Self::Error>>;
+}
+
+impl<T, V> TryFromTraced<V> for T
+where
+    T: TryFrom<V>,
+{
+    #[track_caller]
+    fn try_from_traced(value: V) -> Result<Self, TracedError<Self::Error>> {
+        T::try_from(value).traced()
+    }
+}
+
+/// Error used by `OptionExt::ok_or_missing` when an `Option` is `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingValue {
+    /// The name of the type of the missing value.
+    type_name: &'static str,
+}
+
+impl MissingValue {
+    /// Create a new `MissingValue` for a missing value of type `T`.
+    pub fn of<T>() -> Self {
+        MissingValue {
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    /// The name of the type of the missing value.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl fmt::Display for MissingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "missing value of type `{}`", self.type_name)
+    }
+}
+
+impl Error for MissingValue {}
+
+/// Extension trait for converting an `Option` into a traced `Result`.
+pub trait OptionExt<T> {
+    /// Convert into a traced `Result`, using `error` if the option is `None` and recording the
+    /// call site as the first frame.
+    fn ok_or_traced<E>(self, error: E) -> Result<T, TracedError<E>>;
+
+    /// Convert into a traced `Result`, using a `MissingValue` error if the option is `None` and
+    /// recording the call site as the first frame.
+    fn ok_or_missing(self) -> Result<T, TracedError<MissingValue>>;
+}
+
+impl<T> OptionExt<T> for Option<T> {
+    #[track_caller]
+    fn ok_or_traced<E>(self, error: E) -> Result<T, TracedError<E>> {
+        match self {
+            Some(value) => Ok(value),
+            None => {
+                let mut error = TracedError::new(error);
+                record(&mut error, Location::caller());
+                Err(error)
+            }
+        }
+    }
+
+    #[track_caller]
+    fn ok_or_missing(self) -> Result<T, TracedError<MissingValue>> {
+        match self {
+            Some(value) => Ok(value),
+            None => {
+                let mut error = TracedError::new(MissingValue::of::<T>());
+                record(&mut error, Location::caller());
+                Err(error)
+            }
+        }
+    }
+}
+
+impl<E, S> Traced for TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    fn trace(&mut self, location: &'static Location<'static>) {
+        self.trace.push_frame(TraceFrame::new(location));
+    }
+
+    fn as_trace_log(&self) -> Option<&TraceLog> {
+        self.trace.as_trace_log()
+    }
+
+    fn trace_parent(&self) -> Option<&TraceParent> {
+        self.parent.as_deref()
+    }
+
+    fn fields(&self) -> Option<&Fields> {
+        self.fields.as_deref()
+    }
+
+    fn capture(&mut self, name: &'static str, value: String) {
+        self.trace.push_capture(name, value);
+    }
+
+    fn capture_backtrace(&mut self) {
+        self.trace.push_backtrace(Backtrace::force_capture());
+    }
+}
+
+impl<E, S> From<E> for TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    fn from(error: E) -> Self {
+        TracedError::with_storage(error)
+    }
+}
+
+impl<E, S> fmt::Display for TracedError<E, S>
+where
+    E: fmt::Display,
+    S: TraceStorage,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.mark_handled();
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl<E, S> Error for TracedError<E, S>
+where
+    E: Error,
+    S: TraceStorage + fmt::Debug,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.error.backtrace()
+    }
+}
+
+type BoxError = Box<dyn Error + Send + Sync + 'static>;
+
+/// Type erased error that records the locations it is propagated through.
+///
+/// Like `DynError`, `TracedReport` owns a `Box<dyn Error + Send + Sync>`. Unlike `DynError`,
+/// it doesn't implement `Error` itself, which is what allows it to be constructed with `?` from
+/// any error type.
+pub struct TracedReport {
+    /// The type erased error.
+    error: BoxError,
+    /// The locations the error was propagated through.
+    trace: TraceLog,
+    /// The position of the error in a distributed trace.
+    parent: Option<Box<TraceParent>>,
+    /// The fields set for the whole error.
+    fields: Option<Box<Fields>>,
+}
+
+impl TracedReport {
+    /// Create a new `TracedReport` from an input error, keeping its trace if it has one.
+    ///
+    /// If the error was already traced, the call site is recorded as a "converted here" frame.
+    #[track_caller]
+    pub fn new<E>(error: E) -> Self
+    where
+        E: Error + MaybeTraced + Send + Sync + 'static,
+    {
+        let trace = match trace_log_of(&error) {
+            Some(trace) => {
+                let mut clone = trace.clone();
+                trace.hand_over_watch(&mut clone);
+                record_conversion(&mut clone, Location::caller());
+                clone
+            }
+            None => TraceLog::new(),
+        };
+        let parent = error
+            .as_traced()
+            .and_then(Traced::trace_parent)
+            .map(|parent| Box::new(*parent));
+        let fields = error
+            .as_traced()
+            .and_then(Traced::fields)
+            .filter(|fields| !fields.is_empty())
+            .map(|fields| Box::new(fields.clone()));
+
+        TracedReport {
+            error: Box::new(error),
+            trace,
+            parent,
+            fields,
+        }
+    }
+
+    /// A reference to the type erased error.
+    pub fn inner(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.error
+    }
+
+    /// Attempt to downcast the type erased error to a concrete type.
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where
+        E: Error + 'static,
+    {
+        self.error.downcast_ref()
+    }
+
+    /// The locations the error was propagated through.
+    pub fn trace_log(&self) -> &TraceLog {
+        &self.trace
+    }
+}
+
+impl Traced for TracedReport {
+    fn trace(&mut self, location: &'static Location<'static>) {
+        self.trace.push(location);
+    }
+
+    fn as_trace_log(&self) -> Option<&TraceLog> {
+        Some(&self.trace)
+    }
+
+    fn trace_parent(&self) -> Option<&TraceParent> {
+        self.parent.as_deref()
+    }
+
+    fn fields(&self) -> Option<&Fields> {
+        self.fields.as_deref()
+    }
+
+    fn capture(&mut self, name: &'static str, value: String) {
+        self.trace.capture(name, value);
+    }
+
+    fn capture_backtrace(&mut self) {
+        self.trace.push_backtrace(Backtrace::force_capture());
+    }
+}
+
+impl<E> From<E> for TracedReport
+where
+    E: Error + MaybeTraced + Send + Sync + 'static,
+{
+    #[track_caller]
+    fn from(error: E) -> Self {
+        TracedReport::new(error)
+    }
+}
+
+impl fmt::Display for TracedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.mark_handled();
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+// Like `Report`, this outputs a full multi-line report for situations where you unwrap a
+// `TracedReport` or return it from main.
+impl fmt::Debug for TracedReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", crate::Report::new(self.inner()).pretty(true))?;
+
+        if !self.trace.is_empty() {
+            let heading = setup::heading("Error return trace:");
+            write!(f, "\n\n{}\n{}", heading, self.trace)?;
+        }
+
+        if let Some(parent) = &self.parent {
+            write!(f, "\n\n{} {}", setup::heading("Trace parent:"), parent)?;
+        }
+
+        if !self.fields().is_empty() {
+            write!(f, "\n\n{}", setup::heading("Fields:"))?;
+            for (key, value) in self.fields().iter() {
+                write!(f, "\n    {}: {}", key, value)?;
+            }
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+impl<T, E> Result<T, E> {
+    /// Returns `true` if the result is `Ok`.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Ok(_))
+    }
+
+    /// Returns `true` if the result is `Err`.
+    pub fn is_err(&self) -> bool {
+        !self.is_ok()
+    }
+
+    /// Converts the result into an `Option` of its success value.
+    pub fn ok(self) -> Option<T> {
+        match self {
+            Ok(value) => Some(value),
+            Err(_) => None,
+        }
+    }
+
+    /// Converts the result into an `Option` of its error value.
+    pub fn err(self) -> Option<E> {
+        match self {
+            Ok(_) => None,
+            Err(error) => Some(error),
+        }
+    }
+
+    /// Converts from `&Result<T, E>` to `Result<&T, &E>`.
+    pub fn as_ref(&self) -> Result<&T, &E> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Converts from `&mut Result<T, E>` to `Result<&mut T, &mut E>`.
+    pub fn as_mut(&mut self) -> Result<&mut T, &mut E> {
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Maps the success value, leaving an error untouched.
+    pub fn map<U, F>(self, op: F) -> Result<U, E>
+    where
+        F: FnOnce(T) -> U,
+    {
+        match self {
+            Ok(value) => Ok(op(value)),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Maps the error value, leaving a success value untouched.
+    pub fn map_err<G, F>(self, op: F) -> Result<T, G>
+    where
+        F: FnOnce(E) -> G,
+    {
+        match self {
+            Ok(value) => Ok(value),
+            Err(error) => Err(op(error)),
+        }
+    }
+
+    /// Calls `op` with the success value, otherwise returns the error.
+    pub fn and_then<U, F>(self, op: F) -> Result<U, E>
+    where
+        F: FnOnce(T) -> Result<U, E>,
+    {
+        match self {
+            Ok(value) => op(value),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Returns the success value or the provided default.
+    pub fn unwrap_or(self, default: T) -> T {
+        match self {
+            Ok(value) => value,
+            Err(_) => default,
+        }
+    }
+
+    /// Returns the success value or computes it from the error.
+    pub fn unwrap_or_else<F>(self, op: F) -> T
+    where
+        F: FnOnce(E) -> T,
+    {
+        match self {
+            Ok(value) => value,
+            Err(error) => op(error),
+        }
+    }
+
+    /// Maps the success value with `f`, or returns `default` if there is none.
+    pub fn map_or<U, F>(self, default: U, f: F) -> U
+    where
+        F: FnOnce(T) -> U,
+    {
+        match self {
+            Ok(value) => f(value),
+            Err(_) => default,
+        }
+    }
+
+    /// Maps the success value with `f`, or the error with `default`.
+    pub fn map_or_else<U, D, F>(self, default: D, f: F) -> U
+    where
+        D: FnOnce(E) -> U,
+        F: FnOnce(T) -> U,
+    {
+        match self {
+            Ok(value) => f(value),
+            Err(error) => default(error),
+        }
+    }
+}
+
+// The methods of `std::result::Result` that are still unstable in std
+#[cfg(feature = "unstable-result")]
+impl<T, E> Result<T, E> {
+    /// Returns the success value of a result that can't be an error.
+    pub fn into_ok(self) -> T
+    where
+        E: Into<!>,
+    {
+        match self {
+            Ok(value) => value,
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Returns the error value of a result that can't be a success.
+    pub fn into_err(self) -> E
+    where
+        T: Into<!>,
+    {
+        match self {
+            Ok(value) => value.into(),
+            Err(error) => error,
+        }
+    }
+
+    /// Returns the success value without checking that there is one.
+    ///
+    /// # Safety
+    ///
+    /// Calling this on an `Err` is undefined behavior.
+    #[track_caller]
+    pub unsafe fn unwrap_unchecked(self) -> T {
+        debug_assert!(self.is_ok());
+        match self {
+            Ok(value) => value,
+            Err(_) => std::hint::unreachable_unchecked(),
+        }
+    }
+
+    /// Returns the error value without checking that there is one.
+    ///
+    /// # Safety
+    ///
+    /// Calling this on an `Ok` is undefined behavior.
+    #[track_caller]
+    pub unsafe fn unwrap_err_unchecked(self) -> E {
+        debug_assert!(self.is_err());
+        match self {
+            Ok(_) => std::hint::unreachable_unchecked(),
+            Err(error) => error,
+        }
+    }
+}
+
+#[cfg(feature = "unstable-result")]
+impl<T, E> Result<Result<T, E>, E> {
+    /// Removes one level of nesting from a result of a result.
+    pub fn flatten(self) -> Result<T, E> {
+        match self {
+            Ok(inner) => inner,
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl<T, E> Result<T, E>
+where
+    E: fmt::Debug,
+{
+    /// Returns the success value, panicking with the error if there is none.
+    #[track_caller]
+    pub fn unwrap(self) -> T {
+        match self {
+            Ok(value) => value,
+            Err(error) => panic!("called `Result::unwrap()` on an `Err` value: {:?}", error),
+        }
+    }
+
+    /// Returns the success value, panicking with `msg` and the error if there is none.
+    #[track_caller]
+    pub fn expect(self, msg: &str) -> T {
+        match self {
+            Ok(value) => value,
+            Err(error) => panic!("{}: {:?}", msg, error),
+        }
+    }
+}
+
+impl<T, E> Result<T, E>
+where
+    T: fmt::Debug,
+{
+    /// Returns the error value, panicking with the success value if there is none.
+    #[track_caller]
+    pub fn unwrap_err(self) -> E {
+        match self {
+            Ok(value) => panic!(
+                "called `Result::unwrap_err()` on an `Ok` value: {:?}",
+                value
+            ),
+            Err(error) => error,
+        }
+    }
+}
+
+impl<T, E> Termination for Result<T, E>
+where
+    T: Termination,
+    E: fmt::Debug,
+{
+    /// Return an error code corresponding with the `Result`; 1 for failure.
+    fn report(self) -> i32 {
+        match self {
+            Ok(value) => value.report(),
+            Err(error) => {
+                eprintln!("Error: {:?}", error);
+                1
+            }
+        }
+    }
+}
+
+// `branch` and `from_output` make up the success path of every `?`, so they are always inlined.
+// Passing the location of the `?` to `branch` for `Instrumented` values measurably slows down
+// tight loops even for other types, so it's only done with the `instrumented` feature.
+// `from_residual` is only reached when propagating an error, which is expected to be the uncommon
+// case, so it is kept out of line to avoid bloating callers with conversion and tracing code.
+impl<T, E> Try for Result<T, E>
+where
+    E: MaybeTraced,
+{
+    type Output = T;
+    // Like `std::result::Result<Infallible, E>`, `Result<Infallible, E>` can only ever hold an
+    // error variant
+    type Residual = Result<Infallible, E>;
+
+    #[inline]
+    fn from_output(value: T) -> Self {
+        Ok(value)
+    }
+
+    #[inline]
+    #[cfg_attr(feature = "instrumented", track_caller)]
+    fn branch(self) -> ControlFlow<Self::Residual, T> {
+        match self {
+            #[cfg(feature = "instrumented")]
+            Ok(value) => ControlFlow::Continue(value.maybe_instrument(Location::caller())),
+            #[cfg(not(feature = "instrumented"))]
+            Ok(value) => ControlFlow::Continue(value),
+            Err(error) => ControlFlow::Break(Err(error)),
+        }
+    }
+}
+
+// Given a `Result::Err(E)` emitted by a `?`, convert it and record the location of the `?`
+impl<T, E, F> FromResidual<Result<Infallible, E>> for Result<T, F>
+where
+    F: From<E> + MaybeTraced,
+{
+    #[cold]
+    #[inline(never)]
+    #[track_caller]
+    fn from_residual(residual: Result<Infallible, E>) -> Self {
+        let Err(error) = residual;
+        Err(propagate(error))
+    }
+}
+
+// Given a `std::result::Result::Err(E)` emitted by a `?`, convert it and record the location of
+// the `?`, which will be the first frame of the trace when `F` is a freshly wrapped error
+impl<T, E, F> FromResidual<std::result::Result<Infallible, E>> for Result<T, F>
+where
+    F: From<E> + MaybeTraced,
+{
+    #[cold]
+    #[inline(never)]
+    #[track_caller]
+    fn from_residual(residual: std::result::Result<Infallible, E>) -> Self {
+        let std::result::Result::Err(error) = residual;
+        Err(propagate(error))
+    }
+}
+
+// Given a `Result::Err(E)` emitted by a `?` in a function returning `std::result::Result`, record
+// the location of the `?` and flush the trace to the boundary hook before converting the error
+impl<T, E, F> FromResidual<Result<Infallible, E>> for std::result::Result<T, F>
+where
+    E: MaybeTraced,
+    F: From<E>,
+{
+    #[cold]
+    #[inline(never)]
+    #[track_caller]
+    fn from_residual(residual: Result<Infallible, E>) -> Self {
+        let Err(mut error) = residual;
+        record(&mut error, Location::caller());
+        flush_to_boundary_hook(&error);
+        std::result::Result::Err(F::from(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::num::ParseIntError;
+
+    use pretty_assertions::assert_eq;
+
+    #[derive(Debug)]
+    struct UntracedError;
+
+    impl fmt::Display for UntracedError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "UntracedError is here!")
+        }
+    }
+
+    impl Error for UntracedError {}
+
+    #[cfg(feature = "dyn-dispatch")]
+    impl MaybeTraced for UntracedError {}
+
+    fn std_parse(input: &str) -> std::result::Result<u32, ParseIntError> {
+        input.parse()
+    }
+
+    fn traced_parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+        let value = std_parse(input)?;
+        Ok(value)
+    }
+
+    fn traced_double(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+        let value = traced_parse(input)?;
+        Ok(value * 2)
+    }
+
+    #[test]
+    fn ok_values_propagate_through_question_mark() {
+        assert_eq!(Some(42), traced_double("21").ok());
+    }
+
+    #[test]
+    fn question_mark_on_traced_result_records_each_location() {
+        let error = traced_double("nope").unwrap_err();
+        let frames = error.trace_log().frames();
+
+        assert_eq!(2, frames.len());
+        assert!(frames
+            .iter()
+            .all(|frame| frame.location().file() == file!()));
+        assert!(frames[0].location().line() < frames[1].location().line());
+    }
+
+    #[test]
+    fn question_mark_on_std_result_starts_trace_at_boundary() {
+        let error = traced_parse("nope").unwrap_err();
+        let frames = error.trace_log().frames();
+
+        assert_eq!(1, frames.len());
+        assert_eq!(file!(), frames[0].location().file());
+        assert_eq!("invalid digit found in string", error.to_string());
+    }
+
+    #[test]
+    fn untraced_errors_propagate_unchanged() {
+        fn inner() -> std::result::Result<(), UntracedError> {
+            std::result::Result::Err(UntracedError)
+        }
+
+        fn outer() -> Result<(), UntracedError> {
+            inner()?;
+            Ok(())
+        }
+
+        assert_eq!("UntracedError is here!", outer().unwrap_err().to_string());
+    }
+
+    #[test]
+    fn maybe_traced_only_exposes_traced_errors() {
+        let mut traced = TracedError::new(UntracedError);
+        let mut untraced = "x".parse::<u32>().unwrap_err();
+
+        assert!(traced.as_traced_mut().is_some());
+        assert!(untraced.as_traced_mut().is_none());
+        assert!(untraced.as_traced().is_none());
+    }
+
+    #[cfg(feature = "dyn-dispatch")]
+    #[test]
+    fn as_traced_mut_decides_at_runtime() {
+        enum SometimesTraced {
+            Plain,
+            Traced(TracedError<UntracedError>),
+        }
+
+        impl MaybeTraced for SometimesTraced {
+            fn as_traced_mut(&mut self) -> Option<&mut dyn Traced> {
+                match self {
+                    SometimesTraced::Plain => None,
+                    SometimesTraced::Traced(error) => Some(error),
+                }
+            }
+        }
+
+        fn inner(traced: bool) -> Result<(), SometimesTraced> {
+            if traced {
+                Err(SometimesTraced::Traced(TracedError::new(UntracedError)))
+            } else {
+                Err(SometimesTraced::Plain)
+            }
+        }
+
+        fn outer(traced: bool) -> Result<(), SometimesTraced> {
+            inner(traced)?;
+            Ok(())
+        }
+
+        match outer(true).unwrap_err() {
+            SometimesTraced::Traced(error) => assert_eq!(1, error.trace_log().len()),
+            SometimesTraced::Plain => panic!("expected a traced error"),
+        }
+        assert!(matches!(outer(false).unwrap_err(), SometimesTraced::Plain));
+    }
+
+    #[test]
+    fn question_mark_into_std_result_records_boundary_and_keeps_trace() {
+        fn std_caller(input: &str) -> std::result::Result<u32, TracedError<ParseIntError>> {
+            let value = traced_double(input)?;
+            std::result::Result::Ok(value)
+        }
+
+        let error = std_caller("nope").unwrap_err();
+
+        assert_eq!(3, error.trace_log().len());
+    }
+
+    #[test]
+    fn traced_report_keeps_trace_of_converted_error() {
+        fn report_caller(input: &str) -> Result<u32, TracedReport> {
+            let value = traced_double(input)?;
+            Ok(value)
+        }
+
+        let report = report_caller("nope").unwrap_err();
+        let frames = report.trace_log().frames();
+
+        assert_eq!(4, frames.len());
+        assert_eq!(FrameKind::Converted, frames[2].kind());
+        assert_eq!(FrameKind::Propagated, frames[3].kind());
+        assert_eq!(frames[2].location(), frames[3].location());
+        assert!(report
+            .downcast_ref::<TracedError<ParseIntError>>()
+            .is_some());
+    }
+
+    #[test]
+    fn traced_report_debug_includes_return_trace() {
+        fn report_caller() -> Result<(), TracedReport> {
+            std::result::Result::Err(UntracedError)?;
+            Ok(())
+        }
+
+        let actual = format!("{:?}", report_caller().unwrap_err());
+        let expected = format!(
+            "UntracedError is here!\n\nError return trace:\n   0: {}:",
+            file!()
+        );
+
+        assert!(actual.starts_with(&expected), "{}", actual);
+    }
+
+    #[test]
+    fn patterns_match_whole_paths_with_wildcards() {
+        assert!(matches_pattern("src/lib.rs", "src/lib.rs"));
+        assert!(!matches_pattern("src/lib", "src/lib.rs"));
+        assert!(matches_pattern("*/generated/*", "target/generated/out.rs"));
+        assert!(!matches_pattern("*/generated/*", "src/generator.rs"));
+        assert!(matches_pattern(
+            "*.cargo/registry/*",
+            "/home/me/.cargo/registry/src/a/b.rs"
+        ));
+        assert!(matches_pattern("src/*.rs", "src/return_trace.rs"));
+        assert!(!matches_pattern("src/*.rs", "src/return_trace.rs.bak"));
+        assert!(matches_pattern("*", "anything/at/all.rs"));
+    }
+
+    #[test]
+    fn denylist_drops_matching_frames() {
+        let denylist = FrameDenylist {
+            patterns: vec![String::from("*return_trace.rs")],
+        };
+
+        assert!(denylist.denies(Location::caller()));
+        assert!(!FrameDenylist::default().denies(Location::caller()));
+    }
+
+    #[test]
+    fn convert_keeps_trace_and_records_conversion() {
+        #[derive(Debug)]
+        struct AppError(ParseIntError);
+
+        impl From<ParseIntError> for AppError {
+            fn from(error: ParseIntError) -> Self {
+                AppError(error)
+            }
+        }
+
+        let error = traced_double("nope").unwrap_err();
+        let error: TracedError<AppError> = error.convert();
+        let frame = error.trace_log().frames()[2];
+
+        assert_eq!(3, error.trace_log().len());
+        assert_eq!(FrameKind::Converted, frame.kind());
+        assert!(frame.to_string().ends_with(" (converted here)"));
+    }
+
+    #[test]
+    fn into_traced_records_call_site() {
+        let error = UntracedError.into_traced();
+        let frames = error.trace_log().frames();
+
+        assert_eq!(1, frames.len());
+        assert_eq!(line!() - 4, frames[0].location().line());
+        assert_eq!("UntracedError is here!", error.to_string());
+    }
+
+    #[test]
+    fn traced_converts_std_result_and_records_call_site() {
+        fn migrated(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+            let value = std_parse(input).traced()?;
+            Ok(value)
+        }
+
+        let error = migrated("nope").unwrap_err();
+        let frames = error.trace_log().frames();
+
+        assert_eq!(Some(4), migrated("4").ok());
+        assert_eq!(2, frames.len());
+        assert_eq!(frames[0].location().line(), frames[1].location().line());
+    }
+
+    #[test]
+    fn fallible_conversions_record_call_site() {
+        fn port(value: i64) -> Result<u16, TracedError<std::num::TryFromIntError>> {
+            let port = value.try_into_traced()?;
+            Ok(port)
+        }
+
+        let error = port(-1).unwrap_err();
+        let frames = error.trace_log().frames();
+
+        assert_eq!(Some(8080), port(8080).ok());
+        assert_eq!(2, frames.len());
+        assert_eq!(frames[0].location().line(), frames[1].location().line());
+
+        let error = u8::try_from_traced(300).unwrap_err();
+        assert_eq!(line!() - 1, error.trace_log().frames()[0].location().line());
+    }
+
+    #[test]
+    fn ok_or_traced_records_call_site_for_none() {
+        let error = None::<u32>.ok_or_traced(UntracedError).unwrap_err();
+
+        assert_eq!(line!() - 2, error.trace_log().frames()[0].location().line());
+        assert_eq!("UntracedError is here!", error.to_string());
+        assert_eq!(Some(1), Some(1).ok_or_traced(UntracedError).ok());
+    }
+
+    #[test]
+    fn ok_or_missing_names_missing_type() {
+        let error = None::<u32>.ok_or_missing().unwrap_err();
+
+        assert_eq!(1, error.trace_log().len());
+        assert_eq!("missing value of type `u32`", error.to_string());
+    }
+
+    #[test]
+    fn clones_share_frames_until_they_diverge() {
+        let original = traced_double("x").unwrap_err();
+        let mut clone = original.clone();
+
+        assert!(Arc::ptr_eq(&original.trace.frames, &clone.trace.frames));
+
+        clone.trace(Location::caller());
+
+        assert_eq!(2, original.trace_log().len());
+        assert_eq!(3, clone.trace_log().len());
+        assert_eq!(
+            original.trace_log().frames(),
+            &clone.trace_log().frames()[..2]
+        );
+    }
+
+    #[test]
+    fn frames_are_compact() {
+        let location = Location::caller();
+        let frame = TraceFrame::converted(location);
+
+        assert_eq!(12, std::mem::size_of::<TraceFrame>());
+        assert_eq!(frame.location(), location);
+        assert_eq!(location.to_string(), frame.location().to_string());
+        assert_eq!(FrameKind::Converted, frame.kind());
+    }
+
+    #[test]
+    fn map_or_else_maps_either_variant() {
+        assert_eq!(8, traced_double("4").map_or_else(|_| 0, |value| value));
+        assert_eq!(0, traced_double("nope").map_or_else(|_| 0, |value| value));
+        assert_eq!(1, traced_double("nope").map_or(1, |value| value));
+    }
+
+    #[cfg(feature = "unstable-result")]
+    #[test]
+    fn unstable_methods_match_std() {
+        let ok: Result<u32, !> = Ok(4);
+        assert_eq!(4, ok.into_ok());
+
+        let err: Result<!, &str> = Err("nope");
+        assert_eq!("nope", err.into_err());
+
+        let nested: Result<Result<u32, &str>, &str> = Ok(Err("inner"));
+        assert_eq!(Some("inner"), nested.flatten().err());
+
+        assert_eq!(8, unsafe { traced_double("4").unwrap_unchecked() });
+        let error = unsafe { traced_double("nope").unwrap_err_unchecked() };
+        assert_eq!(2, error.trace_log().len());
+    }
+
+    #[test]
+    fn trace_log_formats_one_frame_per_line() {
+        let error = traced_double("nope").unwrap_err();
+        let actual = error.trace_log().to_string();
+        let lines: Vec<_> = actual.lines().collect();
+
+        assert_eq!(2, lines.len());
+        assert!(lines[0].starts_with("   0: "));
+        assert!(lines[1].starts_with("   1: "));
+    }
+}
@@ -0,0 +1,252 @@
+//! Measuring how long errors take to surface.
+//!
+//! An error that sits in a queue, a retry loop, or a channel nobody reads is usually reported long
+//! after it happened, and by then its trace only says where it went, not when. With hop timing
+//! turned on with `set_hop_timing`, every `TraceLog` records when each of its frames was recorded,
+//! so `TraceLog::age` can tell how long ago the error was created, and `TraceLog::hop_times` how
+//! long it took to reach each hop. A large gap before the last hops is the mark of a failure that
+//! was held on to somewhere before being surfaced.
+//!
+//! Times are read from the clock set with `set_clock`, which defaults to `Instant::now`, so tests
+//! and simulations can run on a clock of their own. Timing is off by default, since it reads the
+//! clock for every frame recorded.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::age::set_hop_timing;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! set_hop_timing(true);
+//!
+//! let error = parse("four").unwrap_err();
+//!
+//! assert!(error.age().is_some());
+//! assert_eq!(1, error.trace_log().hop_times().count());
+//! ```
+
+use std::fmt;
+use std::lazy::SyncLazy;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use super::storage::TraceStorage;
+use super::{
+    push_flags, set_push_flag, TraceFrame, TraceLog, TracedError, TracedReport, HOP_TIMING,
+};
+
+/// A function returning the current time.
+pub type Clock = fn() -> Instant;
+
+static CLOCK: SyncLazy<RwLock<Clock>> = SyncLazy::new(|| RwLock::new(Instant::now));
+
+/// Turn recording the time of every frame on or off.
+///
+/// Only frames recorded while timing is on have a time, so traces that started before it was
+/// turned on have no age.
+pub fn set_hop_timing(enabled: bool) {
+    set_push_flag(HOP_TIMING, enabled);
+}
+
+/// Whether the time of every frame is recorded.
+pub fn hop_timing() -> bool {
+    push_flags() & HOP_TIMING != 0
+}
+
+/// Set the clock frames are timed with, and errors aged against.
+pub fn set_clock(clock: Clock) {
+    *CLOCK.write().unwrap_or_else(|e| e.into_inner()) = clock;
+}
+
+/// The current time, as told by the clock set with `set_clock`.
+pub fn now() -> Instant {
+    let clock = *CLOCK.read().unwrap_or_else(|e| e.into_inner());
+    clock()
+}
+
+/// When a frame of a trace was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HopTime {
+    /// The index of the frame in the trace.
+    frame: usize,
+    /// When the frame was recorded.
+    at: Instant,
+}
+
+impl HopTime {
+    /// A time for the `frame`th frame of a trace.
+    pub(crate) fn new(frame: usize, at: Instant) -> Self {
+        HopTime { frame, at }
+    }
+
+    /// The index of the frame in the trace.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// When the frame was recorded.
+    pub fn at(&self) -> Instant {
+        self.at
+    }
+}
+
+impl TraceLog {
+    /// When the first frame of the trace was recorded, if it was timed.
+    ///
+    /// Traces whose first frames were recorded before timing was turned on have no creation time,
+    /// even if their later frames were timed.
+    pub fn created(&self) -> Option<Instant> {
+        let first = self.hop_times.as_ref()?.first()?;
+
+        if first.frame == 0 {
+            Some(first.at)
+        } else {
+            None
+        }
+    }
+
+    /// How long ago the first frame of the trace was recorded, if it was timed.
+    pub fn age(&self) -> Option<Duration> {
+        self.created()
+            .map(|created| now().saturating_duration_since(created))
+    }
+
+    /// The time of every timed frame, oldest first.
+    pub fn hop_times(&self) -> HopTimes<'_> {
+        HopTimes {
+            trace: self,
+            times: self.hop_times.as_deref().map_or(&[], Vec::as_slice),
+        }
+    }
+
+    /// Record the time of the frame about to be recorded.
+    pub(crate) fn record_hop_time(&mut self) {
+        let time = HopTime::new(self.frames.len(), now());
+        Arc::make_mut(self.hop_times.get_or_insert_with(Default::default)).push(time);
+    }
+}
+
+/// The times the frames of a trace were recorded, which print as how long after the first frame
+/// each frame was recorded.
+#[derive(Debug, Clone, Copy)]
+pub struct HopTimes<'a> {
+    trace: &'a TraceLog,
+    times: &'a [HopTime],
+}
+
+impl<'a> HopTimes<'a> {
+    /// The frame at each timed hop, with how long after the first frame it was recorded.
+    pub fn since_created(&self) -> impl Iterator<Item = (&'a TraceFrame, Duration)> + 'a {
+        let trace = self.trace;
+        let created = self.times.first().map(HopTime::at);
+
+        self.times.iter().filter_map(move |time| {
+            let frame = trace.frames().get(time.frame)?;
+            let created = created?;
+            Some((frame, time.at.saturating_duration_since(created)))
+        })
+    }
+}
+
+impl<'a> Iterator for HopTimes<'a> {
+    type Item = &'a HopTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (first, rest) = self.times.split_first()?;
+        self.times = rest;
+        Some(first)
+    }
+}
+
+impl fmt::Display for HopTimes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (ind, time) in self.times.iter().enumerate() {
+            if ind > 0 {
+                writeln!(f)?;
+            }
+
+            let since = time.at.saturating_duration_since(self.times[0].at);
+            write!(f, "{:>4}: +{:<10}", time.frame, format!("{:?}", since))?;
+            if let Some(frame) = self.trace.frames().get(time.frame) {
+                write!(f, " {}", frame)?;
+            }
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+impl<E, S> TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    /// How long ago the first frame of the error was recorded, if it was timed.
+    ///
+    /// Only traces stored as a `TraceLog` are timed; see `set_hop_timing`.
+    pub fn age(&self) -> Option<Duration> {
+        self.storage().as_trace_log()?.age()
+    }
+}
+
+impl TracedReport {
+    /// How long ago the first frame of the error was recorded, if it was timed.
+    pub fn age(&self) -> Option<Duration> {
+        self.trace_log().age()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+
+    // Tests that turn timing on live in `tests/hop_timing.rs`, since it's a global setting, so
+    // the times here are set by hand
+
+    #[test]
+    fn traces_timed_partway_have_no_age() {
+        let mut trace = TraceLog::new();
+        trace.push(Location::caller());
+        trace.push(Location::caller());
+        assert_eq!(None, trace.age());
+
+        trace.hop_times = Some(Arc::new(vec![HopTime::new(1, Instant::now())]));
+
+        assert_eq!(1, trace.hop_times().count());
+        assert_eq!(None, trace.created());
+        assert_eq!(None, trace.age());
+    }
+
+    #[test]
+    fn hop_times_are_relative_to_the_first_hop() {
+        let mut trace = TraceLog::new();
+        trace.push(Location::caller());
+        trace.push(Location::caller());
+
+        let start = Instant::now();
+        trace.hop_times = Some(Arc::new(vec![
+            HopTime::new(0, start),
+            HopTime::new(1, start + Duration::from_millis(250)),
+        ]));
+        assert_eq!(Some(start), trace.created());
+
+        let since: Vec<_> = trace
+            .hop_times()
+            .since_created()
+            .map(|(_, since)| since)
+            .collect();
+        assert_eq!(vec![Duration::ZERO, Duration::from_millis(250)], since);
+
+        let rendered = trace.hop_times().to_string();
+        let lines: Vec<_> = rendered.lines().collect();
+        assert_eq!(2, lines.len());
+        assert!(lines[1].starts_with("   1: +250ms"));
+        assert!(lines[1].contains("src/return_trace/age.rs:"));
+    }
+}
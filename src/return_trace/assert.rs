@@ -0,0 +1,174 @@
+//! Comparing traced errors in tests.
+//!
+//! Comparing two traced errors with `assert_eq!` prints both of them with `Debug` when they
+//! differ, which for errors with long traces makes it hard to spot what actually changed.
+//! `assert_err_eq!` compares the messages and the frames of two traced errors instead, and when
+//! they differ it panics with a side-by-side view of both, with the rows that differ colored in,
+//! unless colors were turned off with `setup::set_colors`.
+//!
+//! ```rust,should_panic
+//! use std::num::ParseIntError;
+//! use trial_and_error::assert_err_eq;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! // Both errors went through the same `?`, but their messages differ
+//! assert_err_eq!(parse("x").unwrap_err(), parse("").unwrap_err());
+//! ```
+
+use std::fmt::{self, Write};
+
+use super::setup;
+use super::visit::{Collector, Visit};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Assert that two traced errors have the same message and frames.
+///
+/// On failure, this panics with a side-by-side comparison of the two errors. Like `assert_eq!`,
+/// an optional format string and arguments can be given to add a message to the panic.
+#[macro_export]
+macro_rules! assert_err_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::return_trace::assert::assert_err_eq(&$left, &$right, ::std::option::Option::None)
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        $crate::return_trace::assert::assert_err_eq(
+            &$left,
+            &$right,
+            ::std::option::Option::Some(::std::format_args!($($arg)+)),
+        )
+    };
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_err_eq<L, R>(left: &L, right: &R, message: Option<fmt::Arguments<'_>>)
+where
+    L: Visit + ?Sized,
+    R: Visit + ?Sized,
+{
+    let left = rows(&Collector::collect(left));
+    let right = rows(&Collector::collect(right));
+
+    if left == right {
+        return;
+    }
+
+    let mut report = String::from("assertion failed: `(left == right)`");
+    if let Some(message) = message {
+        let _ = write!(report, ": {}", message);
+    }
+    report.push_str("\n\n");
+    side_by_side(&mut report, &left, &right);
+
+    panic!("{}", report);
+}
+
+/// The parts of an error that are compared, one per row of the comparison.
+fn rows(error: &Collector) -> Vec<String> {
+    let mut rows = vec![error.message.clone()];
+    for (index, frame) in error.frames.iter().enumerate() {
+        rows.push(format!("{:>4}: {}", index, frame));
+    }
+    rows
+}
+
+/// Lay out `left` and `right` in two columns, coloring the rows where they differ.
+fn side_by_side(out: &mut String, left: &[String], right: &[String]) {
+    let width = left
+        .iter()
+        .map(|row| row.chars().count())
+        .chain(Some("left".len()))
+        .max()
+        .unwrap_or(0);
+
+    let (red, green, reset) = if setup::colors() {
+        (RED, GREEN, RESET)
+    } else {
+        ("", "", "")
+    };
+
+    let _ = writeln!(out, "  {:width$} │ right", "left", width = width);
+    let _ = writeln!(out, "  {:─<width$}─┼─{:─<5}", "", "", width = width);
+
+    for index in 0..left.len().max(right.len()) {
+        let l = left.get(index).map(String::as_str).unwrap_or("");
+        let r = right.get(index).map(String::as_str).unwrap_or("");
+
+        if l == r {
+            let _ = writeln!(out, "  {:width$} │ {}", l, r, width = width);
+        } else {
+            let _ = writeln!(
+                out,
+                "{}<{} {}{:width$}{} │ {}{}{} {}>{}",
+                red,
+                reset,
+                red,
+                l,
+                reset,
+                green,
+                r,
+                reset,
+                green,
+                reset,
+                width = width
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::ParseIntError;
+    use std::panic;
+
+    use crate::return_trace::{Ok, Result, TracedError};
+
+    fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+        let value = input.parse::<u32>()?;
+        Ok(value)
+    }
+
+    fn double(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+        let value = parse(input)?;
+        Ok(value * 2)
+    }
+
+    #[test]
+    fn equal_errors_pass() {
+        let error = || parse("x").unwrap_err();
+        assert_err_eq!(error(), error());
+    }
+
+    #[test]
+    fn different_errors_panic_with_comparison() {
+        let result = panic::catch_unwind(|| {
+            assert_err_eq!(
+                parse("x").unwrap_err(),
+                double("x").unwrap_err(),
+                "case {}",
+                1
+            );
+        });
+
+        let payload = result.unwrap_err();
+        let report = payload.downcast_ref::<String>().unwrap();
+
+        assert!(report.starts_with("assertion failed: `(left == right)`: case 1\n\n"));
+        assert!(report.contains("  invalid digit found in string"));
+
+        // The first frame is the same, only the second one is missing on the left; rows that
+        // differ are marked whether or not colors are on
+        let differing: Vec<_> = report.lines().filter(|l| l.ends_with('>')).collect();
+        assert_eq!(1, differing.len());
+        assert!(differing[0].contains("   1: src/return_trace/assert.rs:"));
+    }
+}
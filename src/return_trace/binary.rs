@@ -0,0 +1,565 @@
+//! A compact binary encoding of traced errors, for sending them to other processes.
+//!
+//! The frames of a trace refer to `'static` paths in the process that recorded them, so a trace
+//! can't be rebuilt as is on the other side of an RPC. `encode` writes out a traced error's
+//! message, metadata, frames, and `source` chain, storing each file path once, and `decode` reads
+//! them back as a `DecodedTrace`, which can be displayed or inspected like the original. The
+//! encoding is meant to be embedded in the error details of transports like gRPC.
+//!
+//! Transports and log lines often limit how large a payload can get, and a deep trace can blow
+//! past the limit. `encode_with` can be given a frame budget with `EncodeOptions::keep_frames`,
+//! which keeps the first and last frames of a longer trace, where the error was raised and where
+//! it ended up, and only counts the frames in between.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::binary;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let bytes = binary::encode(&parse("four").unwrap_err());
+//! let trace = binary::decode(&bytes).unwrap();
+//!
+//! assert_eq!("invalid digit found in string", trace.message());
+//! assert_eq!(1, trace.frames().len());
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str;
+
+use super::visit::{Collector, Visit};
+use super::FrameKind;
+
+/// Bytes every encoded trace starts with, followed by the version of the encoding.
+const MAGIC: &[u8; 3] = b"TRT";
+
+/// The version of traces with all of their frames.
+const VERSION: u8 = 1;
+
+/// The version of traces with frames elided, which is only used when needed so decoders that
+/// predate elision can read every other trace.
+const VERSION_ELIDED: u8 = 2;
+
+/// Options for `encode_with`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// The number of frames to keep from the start and the end of the trace, if limited.
+    frame_budget: Option<(usize, usize)>,
+}
+
+impl EncodeOptions {
+    /// Create new options that encode every frame.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only the first `head` and the last `tail` frames of traces longer than that, counting
+    /// the frames in between.
+    pub fn keep_frames(mut self, head: usize, tail: usize) -> Self {
+        self.frame_budget = Some((head, tail));
+        self
+    }
+}
+
+/// Frames left out of an encoded trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elision {
+    /// The index of the first elided frame in the original trace.
+    index: usize,
+    /// The number of elided frames.
+    count: usize,
+}
+
+impl Elision {
+    /// The index of the first elided frame in the original trace, which is also the index in
+    /// `DecodedTrace::frames` of the first frame after them.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The number of elided frames.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+/// Encode a traced error.
+pub fn encode<E>(error: &E) -> Vec<u8>
+where
+    E: Visit + ?Sized,
+{
+    encode_with(error, EncodeOptions::new())
+}
+
+/// Encode a traced error according to `options`.
+pub fn encode_with<E>(error: &E, options: EncodeOptions) -> Vec<u8>
+where
+    E: Visit + ?Sized,
+{
+    let mut error = Collector::collect(error);
+
+    let elision = match options.frame_budget {
+        Some((head, tail)) if error.frames.len() > head.saturating_add(tail) => {
+            let count = error.frames.len() - head - tail;
+            error.frames.drain(head..head + count);
+            Some(Elision { index: head, count })
+        }
+        _ => None,
+    };
+
+    let mut out = MAGIC.to_vec();
+    out.push(if elision.is_some() {
+        VERSION_ELIDED
+    } else {
+        VERSION
+    });
+
+    write_str(&mut out, &error.message);
+
+    write_varint(&mut out, error.metadata.len() as u64);
+    for (key, value) in &error.metadata {
+        write_str(&mut out, key);
+        write_str(&mut out, value);
+    }
+
+    // Each path is written the first time a frame uses it, after which frames refer to it by the
+    // order it was written in
+    let mut paths = HashMap::new();
+    write_varint(&mut out, error.frames.len() as u64);
+    for frame in &error.frames {
+        let location = frame.location();
+        let next = paths.len();
+        let id = *paths.entry(location.file()).or_insert(next);
+
+        write_varint(&mut out, id as u64);
+        if id == next {
+            write_str(&mut out, location.file());
+        }
+        write_varint(&mut out, u64::from(location.line()));
+        write_varint(&mut out, u64::from(location.column()));
+
+        match frame.kind() {
+            FrameKind::Propagated => out.push(0),
+            FrameKind::Converted => out.push(1),
+            FrameKind::Sent => {
+                out.push(2);
+                write_str(&mut out, &frame.thread().unwrap_or_default());
+            }
+        }
+    }
+
+    if let Some(elision) = elision {
+        write_varint(&mut out, elision.index as u64);
+        write_varint(&mut out, elision.count as u64);
+    }
+
+    write_varint(&mut out, error.sources.len() as u64);
+    for source in &error.sources {
+        write_str(&mut out, source);
+    }
+
+    out
+}
+
+/// Decode a traced error encoded with `encode`.
+pub fn decode(bytes: &[u8]) -> std::result::Result<DecodedTrace, DecodeError> {
+    let mut reader = Reader { bytes };
+
+    if reader.take(MAGIC.len())? != MAGIC {
+        return std::result::Result::Err(DecodeError(()));
+    }
+    let version = reader.take(1)?[0];
+    if version != VERSION && version != VERSION_ELIDED {
+        return std::result::Result::Err(DecodeError(()));
+    }
+
+    let message = reader.string()?;
+
+    let mut metadata = Vec::new();
+    for _ in 0..reader.varint()? {
+        metadata.push((reader.string()?, reader.string()?));
+    }
+
+    let mut paths: Vec<String> = Vec::new();
+    let mut frames = Vec::new();
+    for _ in 0..reader.varint()? {
+        let id = reader.varint()? as usize;
+        if id == paths.len() {
+            paths.push(reader.string()?);
+        }
+        let file = paths.get(id).cloned().ok_or(DecodeError(()))?;
+        let line = reader.u32()?;
+        let column = reader.u32()?;
+
+        let (kind, thread) = match reader.take(1)?[0] {
+            0 => (FrameKind::Propagated, None),
+            1 => (FrameKind::Converted, None),
+            2 => (FrameKind::Sent, Some(reader.string()?)),
+            _ => return std::result::Result::Err(DecodeError(())),
+        };
+
+        frames.push(DecodedFrame {
+            file,
+            line,
+            column,
+            kind,
+            thread,
+        });
+    }
+
+    let elision = if version == VERSION_ELIDED {
+        let index = reader.varint()? as usize;
+        let count = reader.varint()? as usize;
+        if index > frames.len() {
+            return std::result::Result::Err(DecodeError(()));
+        }
+        Some(Elision { index, count })
+    } else {
+        None
+    };
+
+    let mut sources = Vec::new();
+    for _ in 0..reader.varint()? {
+        sources.push(reader.string()?);
+    }
+
+    if !reader.bytes.is_empty() {
+        return std::result::Result::Err(DecodeError(()));
+    }
+
+    std::result::Result::Ok(DecodedTrace {
+        message,
+        metadata,
+        frames,
+        elision,
+        sources,
+    })
+}
+
+/// A traced error read back from its binary encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedTrace {
+    message: String,
+    metadata: Vec<(String, String)>,
+    frames: Vec<DecodedFrame>,
+    elision: Option<Elision>,
+    sources: Vec<String>,
+}
+
+impl DecodedTrace {
+    /// The message of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The error's metadata, like its type, other than its message.
+    pub fn metadata(&self) -> &[(String, String)] {
+        &self.metadata
+    }
+
+    /// The frames of the trace, oldest first, without the elided ones.
+    pub fn frames(&self) -> &[DecodedFrame] {
+        &self.frames
+    }
+
+    /// The frames left out when the trace was encoded, if any.
+    pub fn elision(&self) -> Option<Elision> {
+        self.elision
+    }
+
+    /// The index the `index`th decoded frame had in the original trace.
+    pub fn original_index(&self, index: usize) -> usize {
+        match self.elision {
+            Some(elision) if index >= elision.index => index + elision.count,
+            _ => index,
+        }
+    }
+
+    /// The messages of the errors in the `source` chain.
+    pub fn sources(&self) -> &[String] {
+        &self.sources
+    }
+}
+
+/// Displays the error like the `Debug` output of a `TracedReport`, with a line in place of any
+/// elided frames.
+impl fmt::Display for DecodedTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+
+        if !self.sources.is_empty() {
+            f.write_str("\n\nCaused by:")?;
+            for (index, source) in self.sources.iter().enumerate() {
+                write!(f, "\n{:>5}: {}", index, source)?;
+            }
+        }
+
+        if !self.frames.is_empty() {
+            f.write_str("\n\nError return trace:")?;
+            for (index, frame) in self.frames.iter().enumerate() {
+                if let Some(elision) = self.elision.filter(|elision| elision.index == index) {
+                    write!(f, "\n      ... {} frames elided ...", elision.count)?;
+                }
+                write!(f, "\n{:>4}: {}", self.original_index(index), frame)?;
+            }
+
+            // Frames elided from the end of the trace have no frame after them to precede
+            if let Some(elision) = self
+                .elision
+                .filter(|elision| elision.index == self.frames.len())
+            {
+                write!(f, "\n      ... {} frames elided ...", elision.count)?;
+            }
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+/// A frame of a `DecodedTrace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedFrame {
+    file: String,
+    line: u32,
+    column: u32,
+    kind: FrameKind,
+    thread: Option<String>,
+}
+
+impl DecodedFrame {
+    /// The name of the source file.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// The line number.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The column number, or 0 for `Sent` frames.
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+
+    /// What happened to the error at this location.
+    pub fn kind(&self) -> FrameKind {
+        self.kind
+    }
+
+    /// The thread that sent the error across a channel, for `Sent` frames.
+    pub fn thread(&self) -> Option<&str> {
+        self.thread.as_deref()
+    }
+}
+
+/// Displays the frame like a `TraceFrame`.
+impl fmt::Display for DecodedFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            FrameKind::Propagated => write!(f, "{}:{}:{}", self.file, self.line, self.column),
+            FrameKind::Converted => write!(
+                f,
+                "{}:{}:{} (converted here)",
+                self.file, self.line, self.column
+            ),
+            FrameKind::Sent => write!(
+                f,
+                "{}:{} (sent across channel by thread {})",
+                self.file,
+                self.line,
+                self.thread.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// Error returned when decoding bytes that aren't an encoded trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError(());
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid encoded trace")
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Write `value` in LEB128.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Write `value` prefixed with its length.
+fn write_str(out: &mut Vec<u8>, value: &str) {
+    write_varint(out, value.len() as u64);
+    out.extend_from_slice(value.as_bytes());
+}
+
+/// Cursor over the bytes left to decode.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return std::result::Result::Err(DecodeError(()));
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        std::result::Result::Ok(taken)
+    }
+
+    fn varint(&mut self) -> std::result::Result<u64, DecodeError> {
+        let mut value = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return std::result::Result::Ok(value);
+            }
+        }
+
+        std::result::Result::Err(DecodeError(()))
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, DecodeError> {
+        let value = self.varint()?;
+        std::convert::TryFrom::try_from(value).map_err(|_| DecodeError(()))
+    }
+
+    fn string(&mut self) -> std::result::Result<String, DecodeError> {
+        let len = self.varint()? as usize;
+        let bytes = self.take(len)?;
+
+        str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|_| DecodeError(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Ok, Result, TracedError, TracedReport};
+
+    fn parse(input: &str) -> Result<u32, TracedError<std::num::ParseIntError>> {
+        let value = input.parse::<u32>()?;
+        Ok(value)
+    }
+
+    fn report(input: &str) -> Result<u32, TracedReport> {
+        let value = parse(input)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn round_trips_frames_and_metadata() {
+        let error = parse("x").unwrap_err();
+        let trace = decode(&encode(&error)).unwrap();
+
+        assert_eq!("invalid digit found in string", trace.message());
+        assert_eq!(
+            &[(
+                "type".to_string(),
+                "core::num::error::ParseIntError".to_string()
+            )],
+            trace.metadata()
+        );
+
+        let frame = &trace.frames()[0];
+        let location = error.trace_log().frames()[0].location();
+        assert_eq!(location.file(), frame.file());
+        assert_eq!(location.line(), frame.line());
+        assert_eq!(location.column(), frame.column());
+        assert_eq!(FrameKind::Propagated, frame.kind());
+    }
+
+    #[test]
+    fn paths_are_written_once() {
+        let error = report("x").unwrap_err();
+        let bytes = encode(&error);
+        let path = file!().as_bytes();
+
+        let count = bytes.windows(path.len()).filter(|w| *w == path).count();
+        assert_eq!(1, count);
+
+        let trace = decode(&bytes).unwrap();
+        assert_eq!(3, trace.frames().len());
+        assert_eq!(FrameKind::Converted, trace.frames()[1].kind());
+        assert_eq!(format!("{:?}", error), trace.to_string());
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let bytes = encode(&parse("x").unwrap_err());
+
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err());
+        }
+        assert!(decode(b"not a trace").is_err());
+    }
+
+    fn nested(depth: usize) -> Result<u32, TracedError<std::num::ParseIntError>> {
+        let value = if depth == 0 {
+            parse("x")?
+        } else {
+            nested(depth - 1)?
+        };
+        Ok(value)
+    }
+
+    #[test]
+    fn frame_budget_keeps_head_and_tail() {
+        let error = nested(9).unwrap_err();
+        let frames = error.trace_log().frames();
+        let options = EncodeOptions::new().keep_frames(2, 3);
+
+        let trace = decode(&encode_with(&error, options)).unwrap();
+        assert_eq!(5, trace.frames().len());
+        assert_eq!(Some(2), trace.elision().map(|elision| elision.index()));
+        assert_eq!(Some(6), trace.elision().map(|elision| elision.count()));
+        assert_eq!(frames[1].location().line(), trace.frames()[1].line());
+        assert_eq!(10, trace.original_index(4));
+
+        let printed = trace.to_string();
+        assert!(printed.contains("\n   1: "));
+        assert!(printed.contains("\n      ... 6 frames elided ...\n   8: "));
+
+        // Traces within the budget are encoded exactly as without one
+        let short = parse("x").unwrap_err();
+        assert_eq!(encode(&short), encode_with(&short, options));
+        assert_eq!(None, decode(&encode(&short)).unwrap().elision());
+    }
+
+    #[test]
+    fn frames_elided_from_the_end_are_marked() {
+        let error = nested(9).unwrap_err();
+        let options = EncodeOptions::new().keep_frames(3, 0);
+
+        let trace = decode(&encode_with(&error, options)).unwrap();
+        assert_eq!(3, trace.frames().len());
+        assert_eq!(Some(3), trace.elision().map(|elision| elision.index()));
+
+        let printed = trace.to_string();
+        assert!(printed.contains("\n   2: "));
+        assert!(printed.ends_with("\n      ... 8 frames elided ..."));
+    }
+}
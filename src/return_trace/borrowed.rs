@@ -0,0 +1,155 @@
+//! Tracing errors that borrow from their input.
+//!
+//! Zero-copy parsers return errors that point into the input they failed on, like an
+//! `Unexpected<'a>` holding a `Cow<'a, str>` of the offending token. Such errors are traced with
+//! `?` like any other: nothing on the way from the `?` to the trace asks for `'static`. What does
+//! is everything that outlives the input, like type erasing the error into a `TracedReport`, or
+//! walking it with a `TraceVisitor`, which hands out `&(dyn Error + 'static)` so the error can be
+//! downcast.
+//!
+//! Borrowed errors cross into that world by implementing `IntoOwned`, which turns them into an
+//! owned version of themselves. `TracedError::into_owned` does so without touching the trace, so
+//! the allocation only happens once the error leaves the parser, not for every error it returns.
+//!
+//! ```rust
+//! use std::borrow::Cow;
+//! use std::error::Error;
+//! use std::fmt;
+//! use trial_and_error::return_trace::borrowed::IntoOwned;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError, TracedReport};
+//!
+//! #[derive(Debug)]
+//! struct Unexpected<'a> {
+//!     token: Cow<'a, str>,
+//! }
+//!
+//! impl fmt::Display for Unexpected<'_> {
+//!     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+//!         write!(f, "unexpected `{}`", self.token)
+//!     }
+//! }
+//!
+//! impl Error for Unexpected<'_> {}
+//!
+//! impl IntoOwned for Unexpected<'_> {
+//!     type Owned = Unexpected<'static>;
+//!
+//!     fn into_owned(self) -> Self::Owned {
+//!         Unexpected { token: self.token.into_owned().into() }
+//!     }
+//! }
+//!
+//! fn token(input: &str) -> Result<&str, TracedError<Unexpected<'_>>> {
+//!     if let Some(token) = input.strip_prefix('!') {
+//!         std::result::Result::<(), _>::Err(Unexpected { token: token.into() })?;
+//!     }
+//!
+//!     Ok(input)
+//! }
+//!
+//! fn parse(input: &str) -> Result<&str, TracedError<Unexpected<'_>>> {
+//!     let token = token(input)?;
+//!
+//!     Ok(token)
+//! }
+//!
+//! let input = String::from("!oops");
+//! let error = parse(&input).unwrap_err().into_owned();
+//! drop(input);
+//!
+//! assert_eq!(2, error.trace_log().len());
+//! assert_eq!("unexpected `oops`", TracedReport::new(error).to_string());
+//! ```
+
+use std::borrow::Cow;
+
+use super::TracedError;
+
+/// An error that may borrow from its input, and can be turned into an owned version of itself.
+pub trait IntoOwned {
+    /// The error, without any borrows.
+    type Owned: 'static;
+
+    /// Turn the error into an owned version of itself, copying whatever it borrows.
+    fn into_owned(self) -> Self::Owned;
+}
+
+impl IntoOwned for &str {
+    type Owned = String;
+
+    fn into_owned(self) -> Self::Owned {
+        self.to_string()
+    }
+}
+
+impl<B> IntoOwned for Cow<'_, B>
+where
+    B: ToOwned + ?Sized + 'static,
+{
+    type Owned = Cow<'static, B>;
+
+    fn into_owned(self) -> Cow<'static, B> {
+        Cow::Owned(Cow::into_owned(self))
+    }
+}
+
+impl<E, S> TracedError<E, S>
+where
+    E: IntoOwned,
+{
+    /// Turn the wrapped error into an owned version of itself, keeping the trace as it is.
+    ///
+    /// Unlike `convert`, this doesn't record a frame, since the error is still the same error.
+    pub fn into_owned(self) -> TracedError<E::Owned, S> {
+        TracedError {
+            error: self.error.into_owned(),
+            trace: self.trace,
+            parent: self.parent,
+            fields: self.fields,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Ok, Result};
+
+    fn first_word(input: &str) -> Result<&str, TracedError<&str>> {
+        let word = input.split_whitespace().next();
+        let word = word.ok_or(input);
+
+        Ok(word?)
+    }
+
+    fn words(input: &str) -> Result<&str, TracedError<&str>> {
+        let word = first_word(input)?;
+
+        Ok(word)
+    }
+
+    #[test]
+    fn borrowed_errors_are_traced() {
+        let input = String::from("   ");
+        let error = words(&input).unwrap_err();
+
+        assert_eq!("   ", *error.inner());
+        assert_eq!(2, error.trace_log().len());
+    }
+
+    #[test]
+    fn owned_errors_keep_their_trace() {
+        let input = String::from("   ");
+        let error = words(&input).unwrap_err().with_field("input", "blank");
+        let owned: TracedError<String> = error.into_owned();
+        drop(input);
+
+        assert_eq!(2, owned.trace_log().len());
+        assert_eq!(Some(&"blank".into()), owned.field("input"));
+
+        let cow: TracedError<Cow<'static, str>> =
+            TracedError::new(Cow::Borrowed("borrowed")).into_owned();
+        assert!(matches!(cow.inner(), Cow::Owned(_)));
+    }
+}
@@ -0,0 +1,172 @@
+//! Attaching the values of local variables to the frames of a trace.
+//!
+//! A file and line say where an error went, but not what the program was working on when it got
+//! there. `capture!` formats the named locals with `Debug` and attaches them to the next frame
+//! recorded into a traced error, which is usually the `?` right after it, so they show up under
+//! that frame when the trace is printed and are handed to a `TraceVisitor` along with it.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::capture;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str, attempt: u32) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input
+//!         .parse::<u32>()
+//!         .map_err(|error| capture!(TracedError::new(error), input, attempt))?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let error = parse("four", 3).unwrap_err();
+//! let captures = error.trace_log().captures();
+//!
+//! assert_eq!("input", captures[0].name());
+//! assert_eq!("\"four\"", captures[0].value());
+//! assert_eq!("3", captures[1].value());
+//! ```
+
+use std::sync::Arc;
+
+use super::TraceLog;
+
+/// The value of a local variable, attached to a frame of a trace by `capture!`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    /// The index of the frame the value is attached to.
+    frame: usize,
+    /// The name of the variable.
+    name: &'static str,
+    /// The `Debug` representation of the value.
+    value: String,
+}
+
+impl Capture {
+    /// The index of the frame the value is attached to, counting from the first location
+    /// recorded.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// The name of the variable.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The value of the variable, formatted with `Debug`.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl TraceLog {
+    /// The values captured with `capture!`, in the order they were captured.
+    pub fn captures(&self) -> &[Capture] {
+        self.captures.as_deref().map_or(&[], Vec::as_slice)
+    }
+
+    /// The values captured for the `index`th frame.
+    pub fn captures_of(&self, index: usize) -> impl Iterator<Item = &Capture> + '_ {
+        self.captures()
+            .iter()
+            .filter(move |capture| capture.frame == index)
+    }
+
+    /// Attach `value` to the next frame recorded.
+    pub fn capture(&mut self, name: &'static str, value: String) {
+        let frame = self.frames.len();
+        let captures = self.captures.get_or_insert_with(Default::default);
+
+        Arc::make_mut(captures).push(Capture { frame, name, value });
+        self.account();
+    }
+}
+
+/// Attach the values of local variables to the next frame recorded into a traced error.
+///
+/// Takes the error, followed by the names of the variables, and evaluates to the error. Each value
+/// is formatted with `Debug` when the macro runs. Errors whose trace isn't kept in a `TraceLog`
+/// ignore the captured values.
+///
+/// ```rust
+/// use trial_and_error::capture;
+/// use trial_and_error::return_trace::{MissingValue, Ok, OptionExt, Result, TracedError};
+///
+/// fn user(id: usize, attempt: u32) -> Result<&'static str, TracedError<MissingValue>> {
+///     let users = ["ada", "grace"];
+///     let user = users
+///         .get(id)
+///         .ok_or_missing()
+///         .map_err(|error| capture!(error, id, attempt))?;
+///
+///     Ok(*user)
+/// }
+///
+/// let error = user(7, 2).unwrap_err();
+///
+/// assert_eq!(2, error.trace_log().captures().len());
+/// assert!(error.trace_log().to_string().contains("id = 7"));
+/// ```
+#[macro_export]
+macro_rules! capture {
+    ($error:expr, $($name:ident),+ $(,)?) => {{
+        let mut error = $error;
+        $(
+            $crate::return_trace::Traced::capture(
+                &mut error,
+                ::std::stringify!($name),
+                ::std::format!("{:?}", $name),
+            );
+        )+
+        error
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::return_trace::{MissingValue, Ok, Result, TracedError};
+
+    fn lookup(key: &str, attempt: u32) -> Result<(), TracedError<MissingValue>> {
+        std::result::Result::Err(MissingValue::of::<u32>())
+            .map_err(|error| capture!(TracedError::new(error), key, attempt))?;
+        Ok(())
+    }
+
+    fn retry(key: &str) -> Result<(), TracedError<MissingValue>> {
+        let backoff = 250;
+        lookup(key, 1).map_err(|error| capture!(error, backoff))?;
+        Ok(())
+    }
+
+    #[test]
+    fn captures_attach_to_the_next_frame() {
+        let error = retry("port").unwrap_err();
+        let trace = error.trace_log();
+
+        assert_eq!(2, trace.len());
+        assert_eq!(
+            vec![("key", "\"port\""), ("attempt", "1"), ("backoff", "250")],
+            trace
+                .captures()
+                .iter()
+                .map(|capture| (capture.name(), capture.value()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, trace.captures_of(0).count());
+        assert_eq!(1, trace.captures_of(1).count());
+    }
+
+    #[test]
+    fn captures_are_printed_under_their_frame() {
+        let error = retry("port").unwrap_err();
+        let printed = error.trace_log().to_string();
+        let lines: Vec<_> = printed.lines().collect();
+
+        assert_eq!(5, lines.len());
+        assert!(lines[0].starts_with("   0: "));
+        assert_eq!("        key = \"port\"", lines[1]);
+        assert_eq!("        attempt = 1", lines[2]);
+        assert!(lines[3].starts_with("   1: "));
+        assert_eq!("        backoff = 250", lines[4]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Recording where traced errors cross a channel.
+//!
+//! Errors reported from one thread to another through a channel jump from the sender's stack to
+//! the receiver's without a `?` in between, so their trace silently skips the hand-off.
+//! `TracedError::crossed_channel` records the hand-off as a frame of its own, which also names the
+//! thread the error was sent from. `TracedSender` wraps a `std::sync::mpsc::Sender` to do this for
+//! every error it sends.
+//!
+//! ```rust
+//! use std::sync::mpsc;
+//! use std::thread;
+//! use trial_and_error::return_trace::channel::TracedSender;
+//! use trial_and_error::return_trace::{FrameKind, MissingValue, OptionExt, TracedError};
+//!
+//! let (sender, receiver) = mpsc::channel::<TracedError<MissingValue>>();
+//! let sender = TracedSender::new(sender);
+//!
+//! thread::Builder::new()
+//!     .name("worker".into())
+//!     .spawn(move || {
+//!         let error = None::<u32>.ok_or_missing().unwrap_err();
+//!         sender.send(error).unwrap();
+//!     })
+//!     .unwrap()
+//!     .join()
+//!     .unwrap();
+//!
+//! let error = receiver.recv().unwrap();
+//! let sent = error.trace_log().frames()[1];
+//!
+//! assert_eq!(FrameKind::Sent, sent.kind());
+//! assert_eq!(Some("worker".to_string()), sent.thread());
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::lazy::SyncLazy;
+use std::panic::Location;
+use std::sync::mpsc::{SendError, Sender};
+use std::sync::RwLock;
+use std::thread::{self, ThreadId};
+
+use super::storage::TraceStorage;
+use super::{is_denylisted, trace_mode, TraceFrame, TraceMode, TracedError};
+
+/// Label index used once the table of thread labels is full.
+const OVERFLOW_LABEL: u16 = u16::MAX;
+
+#[derive(Default)]
+struct ThreadLabels {
+    /// The index of the label of every thread that has sent an error.
+    ids: HashMap<ThreadId, u16>,
+    /// The labels, by index.
+    labels: Vec<String>,
+}
+
+static THREAD_LABELS: SyncLazy<RwLock<ThreadLabels>> =
+    SyncLazy::new(|| RwLock::new(ThreadLabels::default()));
+
+/// The index of the label of the current thread, which is its name if it has one.
+pub(crate) fn current_thread_label() -> u16 {
+    let thread = thread::current();
+
+    if let Some(&id) = THREAD_LABELS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .ids
+        .get(&thread.id())
+    {
+        return id;
+    }
+
+    let mut labels = THREAD_LABELS.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(&id) = labels.ids.get(&thread.id()) {
+        return id;
+    }
+
+    let id = match u16::try_from(labels.labels.len()) {
+        std::result::Result::Ok(id) if id != OVERFLOW_LABEL => id,
+        _ => return OVERFLOW_LABEL,
+    };
+
+    let label = match thread.name() {
+        Some(name) => name.to_string(),
+        None => format!("{:?}", thread.id()),
+    };
+    labels.labels.push(label);
+    labels.ids.insert(thread.id(), id);
+    id
+}
+
+/// The label at `id`.
+pub(crate) fn thread_label(id: u16) -> String {
+    THREAD_LABELS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .labels
+        .get(usize::from(id))
+        .cloned()
+        .unwrap_or_else(|| "<unknown>".to_string())
+}
+
+impl<E, S> TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    /// Record that the error is being sent across a channel from the call site, by the current
+    /// thread.
+    #[track_caller]
+    pub fn crossed_channel(mut self) -> Self {
+        let location = Location::caller();
+
+        if !is_denylisted(location) && trace_mode() == TraceMode::Full {
+            self.trace.push_frame(TraceFrame::sent(location));
+        }
+
+        self
+    }
+}
+
+/// A `std::sync::mpsc::Sender` of traced errors that records every error it sends as having
+/// crossed the channel.
+#[derive(Debug)]
+pub struct TracedSender<E, S = super::TraceLog> {
+    sender: Sender<TracedError<E, S>>,
+}
+
+impl<E, S> TracedSender<E, S>
+where
+    S: TraceStorage,
+{
+    /// Wrap a `Sender`.
+    pub fn new(sender: Sender<TracedError<E, S>>) -> Self {
+        TracedSender { sender }
+    }
+
+    /// Send an error, recording the call site as the point where it crossed the channel.
+    #[track_caller]
+    pub fn send(
+        &self,
+        error: TracedError<E, S>,
+    ) -> std::result::Result<(), SendError<TracedError<E, S>>> {
+        self.sender.send(error.crossed_channel())
+    }
+
+    /// Unwrap the `Sender`.
+    pub fn into_inner(self) -> Sender<TracedError<E, S>> {
+        self.sender
+    }
+}
+
+impl<E, S> Clone for TracedSender<E, S> {
+    fn clone(&self) -> Self {
+        TracedSender {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    use crate::return_trace::{FrameKind, MissingValue, OptionExt};
+
+    #[test]
+    fn crossed_channel_records_thread_and_line() {
+        let error = None::<u32>.ok_or_missing().unwrap_err();
+        let line = line!() + 1;
+        let error = error.crossed_channel();
+
+        let frame = error.trace_log().frames()[1];
+        assert_eq!(FrameKind::Sent, frame.kind());
+        assert_eq!(line, frame.location().line());
+        assert_eq!(0, frame.location().column());
+
+        let thread = thread::current();
+        let expected = thread.name().unwrap();
+        assert_eq!(Some(expected.to_string()), frame.thread());
+
+        let display = frame.to_string();
+        assert!(display.ends_with(&format!(
+            ":{} (sent across channel by thread {})",
+            line, expected
+        )));
+    }
+
+    #[test]
+    fn sender_records_unnamed_threads_by_id() {
+        let (sender, receiver) = mpsc::channel::<TracedError<MissingValue>>();
+        let sender = TracedSender::new(sender);
+
+        let id = thread::spawn(move || {
+            sender
+                .send(None::<u32>.ok_or_missing().unwrap_err())
+                .unwrap();
+            thread::current().id()
+        })
+        .join()
+        .unwrap();
+
+        let error = receiver.recv().unwrap();
+        let frame = error.trace_log().frames()[1];
+        assert_eq!(Some(format!("{:?}", id)), frame.thread());
+    }
+}
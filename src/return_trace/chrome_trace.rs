@@ -0,0 +1,285 @@
+//! Exporting the timing of traced errors in the Chrome trace event format.
+//!
+//! With hop timing on, every frame of a trace knows when it was recorded, which says where an
+//! error spent its time on the way up: waiting in a queue, crossing a channel, or being retried.
+//! A `ChromeTrace` collects timed errors and writes them as the JSON that `chrome://tracing`
+//! and Perfetto load, so they can be looked at next to the profile of the program that produced
+//! them.
+//!
+//! Every error gets a track of its own, named after its message. The viewers draw tracks as
+//! threads, but there is one per error, not one per thread or task the error passed through,
+//! since traces don't record where each frame ran. Each hop is a slice that lasts until the next
+//! hop was recorded, and the last hop, where the error was collected, is an instant event carrying
+//! the error's metadata. Timestamps are in microseconds since the start of the trace, which is the
+//! first hop of the first error unless `start` sets it, for example to the start of another
+//! profile the errors should line up with. Errors without hop times can't be placed on the
+//! timeline, and are skipped.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::age::set_hop_timing;
+//! use trial_and_error::return_trace::chrome_trace::ChromeTrace;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! fn load(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = parse(input)?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! set_hop_timing(true);
+//!
+//! let mut trace = ChromeTrace::new();
+//! trace.add(&load("four").unwrap_err());
+//!
+//! let json = trace.to_json();
+//! assert!(json.starts_with("{\"traceEvents\":["));
+//! assert!(json.contains("\"ph\":\"X\""));
+//! ```
+
+use std::fmt::{self, Write};
+use std::time::{Duration, Instant};
+
+use super::age::HopTime;
+use super::response::Json;
+use super::visit::{TraceVisitor, Visit};
+use super::{FrameKind, TraceFrame};
+
+/// Process ID the events are reported under.
+const PID: u32 = 1;
+
+/// Collects timed traced errors and writes them as Chrome trace events.
+#[derive(Debug, Clone, Default)]
+pub struct ChromeTrace {
+    /// When the trace starts, if it was set.
+    start: Option<Instant>,
+    errors: Vec<TimedError>,
+    /// The number of errors that had no hop times.
+    skipped: usize,
+}
+
+/// The parts of a traced error that are exported.
+#[derive(Debug, Clone)]
+struct TimedError {
+    message: String,
+    metadata: Vec<(String, String)>,
+    /// The timed frames, oldest first.
+    hops: Vec<(TraceFrame, Instant)>,
+}
+
+impl ChromeTrace {
+    /// Create a new, empty `ChromeTrace` that starts at the first hop of the first error.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the trace at `start`, so its timestamps line up with other profiles starting then.
+    ///
+    /// Hops recorded before `start` are placed at the start of the trace.
+    pub fn start(mut self, start: Instant) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    /// Add a traced error to the trace, returning whether it had hop times to export.
+    pub fn add<E>(&mut self, error: &E) -> bool
+    where
+        E: Visit + ?Sized,
+    {
+        let mut timings = Timings::default();
+        error.visit(&mut timings);
+
+        let hops: Vec<_> = timings
+            .times
+            .iter()
+            .filter_map(|time| Some((*timings.frames.get(time.frame())?, time.at())))
+            .collect();
+
+        if hops.is_empty() {
+            self.skipped += 1;
+            return false;
+        }
+
+        self.errors.push(TimedError {
+            message: timings.message,
+            metadata: timings.metadata,
+            hops,
+        });
+        true
+    }
+
+    /// The number of errors added to the trace, including those that were skipped.
+    pub fn len(&self) -> usize {
+        self.errors.len() + self.skipped
+    }
+
+    /// Whether no errors were added to the trace.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of errors that were skipped because they had no hop times.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Write the trace as a JSON object with a `traceEvents` array.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        // Writing to a `String` can't fail
+        let _ = self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut String) -> fmt::Result {
+        let start = self.start.or_else(|| {
+            self.errors
+                .iter()
+                .filter_map(|error| error.hops.first().map(|(_, at)| *at))
+                .min()
+        });
+
+        out.push_str("{\"traceEvents\":[");
+        let mut events = Events { out, count: 0 };
+
+        for (index, error) in self.errors.iter().enumerate() {
+            // Each error is a track of its own, not the thread it was recorded on
+            let tid = index + 1;
+            let ts = |at: Instant| {
+                micros(start.map_or(Duration::ZERO, |start| at.saturating_duration_since(start)))
+            };
+
+            events.next()?;
+            write!(
+                events.out,
+                concat!(
+                    "{{\"name\":\"thread_name\",\"ph\":\"M\",",
+                    "\"pid\":{},\"tid\":{},\"args\":{{\"name\":{}}}}}"
+                ),
+                PID,
+                tid,
+                Json(&error.message)
+            )?;
+
+            for (hop, (frame, at)) in error.hops.iter().enumerate() {
+                events.next()?;
+                write!(
+                    events.out,
+                    "{{\"name\":{},\"cat\":\"error\",\"pid\":{},\"tid\":{},\"ts\":{:.3},",
+                    Json(&frame.to_string()),
+                    PID,
+                    tid,
+                    ts(*at)
+                )?;
+
+                match error.hops.get(hop + 1) {
+                    Some((_, next)) => write!(
+                        events.out,
+                        "\"ph\":\"X\",\"dur\":{:.3},\"args\":{{\"kind\":{}}}}}",
+                        micros(next.saturating_duration_since(*at)),
+                        Json(kind_name(frame.kind()))
+                    )?,
+                    None => {
+                        write!(
+                            events.out,
+                            "\"ph\":\"i\",\"s\":\"t\",\"args\":{{\"kind\":{}",
+                            Json(kind_name(frame.kind()))
+                        )?;
+                        for (key, value) in &error.metadata {
+                            write!(events.out, ",{}:{}", Json(key), Json(value))?;
+                        }
+                        events.out.push_str("}}");
+                    }
+                }
+            }
+        }
+
+        out.push_str("],\"displayTimeUnit\":\"ms\"}");
+        fmt::Result::Ok(())
+    }
+}
+
+/// Writes the commas between events.
+struct Events<'a> {
+    out: &'a mut String,
+    count: usize,
+}
+
+impl Events<'_> {
+    /// Start the next event.
+    fn next(&mut self) -> fmt::Result {
+        if self.count > 0 {
+            self.out.push(',');
+        }
+        self.count += 1;
+        fmt::Result::Ok(())
+    }
+}
+
+/// Microseconds, the unit of Chrome trace event timestamps.
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+fn kind_name(kind: FrameKind) -> &'static str {
+    match kind {
+        FrameKind::Propagated => "propagated",
+        FrameKind::Converted => "converted",
+        FrameKind::Sent => "sent",
+    }
+}
+
+/// Visitor that collects the frames of an error with their times.
+#[derive(Default)]
+struct Timings {
+    message: String,
+    metadata: Vec<(String, String)>,
+    frames: Vec<TraceFrame>,
+    times: Vec<HopTime>,
+}
+
+impl TraceVisitor for Timings {
+    fn visit_frame(&mut self, _index: usize, frame: &TraceFrame) {
+        self.frames.push(*frame);
+    }
+
+    fn visit_hop_time(&mut self, _frame: usize, time: &HopTime) {
+        self.times.push(*time);
+    }
+
+    fn visit_metadata(&mut self, key: &str, value: &dyn fmt::Display) {
+        if key == "message" {
+            self.message = value.to_string();
+        } else {
+            self.metadata.push((key.to_string(), value.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{MissingValue, TracedError};
+
+    // Tests that time real hops live in `tests/hop_timing.rs`, since timing is a global setting
+
+    #[test]
+    fn untimed_errors_are_skipped() {
+        let mut trace = ChromeTrace::new();
+        assert!(!trace.add(&TracedError::new(MissingValue::of::<u8>())));
+
+        assert_eq!(1, trace.len());
+        assert_eq!(1, trace.skipped());
+        assert_eq!(
+            "{\"traceEvents\":[],\"displayTimeUnit\":\"ms\"}",
+            trace.to_json()
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Tracing composite errors through one of their parts.
+//!
+//! Errors are often layered with context by pairing them with it, either in a tuple or in a struct
+//! with a field for the error. Rather than implementing `Traced` for each of these composites, an
+//! `(E, C)` pair is `Traced` whenever `E` is, recording frames into `E`, and
+//! `delegate_traced!` does the same for a struct, through the field holding its error, or for an
+//! enum, through the error held by whichever variant it is.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! type WithInput = (TracedError<ParseIntError>, String);
+//!
+//! fn parse(input: &str) -> Result<u32, WithInput> {
+//!     let value = input
+//!         .parse::<u32>()
+//!         .map_err(|error| (TracedError::new(error), input.to_string()))?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! fn double(input: &str) -> Result<u32, WithInput> {
+//!     let value = parse(input)?;
+//!
+//!     Ok(value * 2)
+//! }
+//!
+//! let (error, input) = double("four").unwrap_err();
+//!
+//! assert_eq!("four", input);
+//! assert_eq!(2, error.trace_log().len());
+//! ```
+
+use std::panic::Location;
+
+use super::fields::Fields;
+use super::traceparent::TraceParent;
+use super::{TraceLog, Traced};
+
+impl<E, C> Traced for (E, C)
+where
+    E: Traced,
+{
+    fn trace(&mut self, location: &'static Location<'static>) {
+        self.0.trace(location);
+    }
+
+    fn as_trace_log(&self) -> Option<&TraceLog> {
+        self.0.as_trace_log()
+    }
+
+    fn trace_parent(&self) -> Option<&TraceParent> {
+        self.0.trace_parent()
+    }
+
+    fn fields(&self) -> Option<&Fields> {
+        self.0.fields()
+    }
+
+    fn capture(&mut self, name: &'static str, value: String) {
+        self.0.capture(name, value);
+    }
+
+    fn capture_backtrace(&mut self) {
+        self.0.capture_backtrace();
+    }
+}
+
+/// Implement `Traced` for a struct by recording frames into one of its fields, or for an enum by
+/// recording them into the first field of its variant.
+///
+/// Like any other implementation of `Traced`, this needs `#![feature(min_specialization)]` unless
+/// the `dyn-dispatch` feature is enabled.
+///
+/// ```rust
+/// #![feature(min_specialization)]
+///
+/// use std::num::ParseIntError;
+/// use trial_and_error::delegate_traced;
+/// use trial_and_error::return_trace::{Ok, Result, TracedError};
+///
+/// struct ConfigError {
+///     key: &'static str,
+///     source: TracedError<ParseIntError>,
+/// }
+///
+/// delegate_traced!(ConfigError, source);
+///
+/// fn port() -> Result<u16, ConfigError> {
+///     let port = "http"
+///         .parse::<u16>()
+///         .map_err(|error| ConfigError { key: "port", source: TracedError::new(error) })?;
+///
+///     Ok(port)
+/// }
+///
+/// let error = port().unwrap_err();
+///
+/// assert_eq!("port", error.key);
+/// assert_eq!(1, error.source.trace_log().len());
+/// ```
+///
+/// For an enum, every variant has to be listed, and has to hold a traced error as its first field.
+/// Converting the inner error into the enum keeps its trace, so layered error enums record one
+/// continuous trace instead of a partial one at each layer.
+///
+/// ```rust
+/// #![feature(min_specialization)]
+///
+/// use std::num::{ParseFloatError, ParseIntError};
+/// use trial_and_error::delegate_traced;
+/// use trial_and_error::return_trace::{Ok, Result, TracedError};
+///
+/// enum ConfigError {
+///     Port(TracedError<ParseIntError>),
+///     Ratio(TracedError<ParseFloatError>, &'static str),
+/// }
+///
+/// delegate_traced!(enum ConfigError { Port, Ratio });
+///
+/// impl From<TracedError<ParseIntError>> for ConfigError {
+///     fn from(error: TracedError<ParseIntError>) -> Self {
+///         ConfigError::Port(error)
+///     }
+/// }
+///
+/// fn parse_port(input: &str) -> Result<u16, TracedError<ParseIntError>> {
+///     let port = input.parse::<u16>()?;
+///
+///     Ok(port)
+/// }
+///
+/// fn port() -> Result<u16, ConfigError> {
+///     let port = parse_port("http")?;
+///
+///     Ok(port)
+/// }
+///
+/// match port().unwrap_err() {
+///     ConfigError::Port(error) => assert_eq!(2, error.trace_log().len()),
+///     ConfigError::Ratio(..) => unreachable!(),
+/// }
+/// ```
+#[macro_export]
+macro_rules! delegate_traced {
+    (enum $ty:ty { $($variant:ident),+ $(,)? }) => {
+        impl $crate::return_trace::Traced for $ty {
+            fn trace(&mut self, location: &'static ::std::panic::Location<'static>) {
+                match self {
+                    $(Self::$variant(error, ..) => {
+                        $crate::return_trace::Traced::trace(error, location)
+                    })+
+                }
+            }
+
+            fn as_trace_log(&self) -> ::std::option::Option<&$crate::return_trace::TraceLog> {
+                match self {
+                    $(Self::$variant(error, ..) => {
+                        $crate::return_trace::Traced::as_trace_log(error)
+                    })+
+                }
+            }
+
+            fn trace_parent(
+                &self,
+            ) -> ::std::option::Option<&$crate::return_trace::traceparent::TraceParent> {
+                match self {
+                    $(Self::$variant(error, ..) => {
+                        $crate::return_trace::Traced::trace_parent(error)
+                    })+
+                }
+            }
+
+            fn fields(&self) -> ::std::option::Option<&$crate::return_trace::fields::Fields> {
+                match self {
+                    $(Self::$variant(error, ..) => $crate::return_trace::Traced::fields(error),)+
+                }
+            }
+
+            fn capture(&mut self, name: &'static str, value: ::std::string::String) {
+                match self {
+                    $(Self::$variant(error, ..) => {
+                        $crate::return_trace::Traced::capture(error, name, value)
+                    })+
+                }
+            }
+
+            fn capture_backtrace(&mut self) {
+                match self {
+                    $(Self::$variant(error, ..) => {
+                        $crate::return_trace::Traced::capture_backtrace(error)
+                    })+
+                }
+            }
+        }
+    };
+    ($ty:ty, $field:tt) => {
+        impl $crate::return_trace::Traced for $ty {
+            fn trace(&mut self, location: &'static ::std::panic::Location<'static>) {
+                $crate::return_trace::Traced::trace(&mut self.$field, location)
+            }
+
+            fn as_trace_log(&self) -> ::std::option::Option<&$crate::return_trace::TraceLog> {
+                $crate::return_trace::Traced::as_trace_log(&self.$field)
+            }
+
+            fn trace_parent(
+                &self,
+            ) -> ::std::option::Option<&$crate::return_trace::traceparent::TraceParent> {
+                $crate::return_trace::Traced::trace_parent(&self.$field)
+            }
+
+            fn fields(&self) -> ::std::option::Option<&$crate::return_trace::fields::Fields> {
+                $crate::return_trace::Traced::fields(&self.$field)
+            }
+
+            fn capture(&mut self, name: &'static str, value: ::std::string::String) {
+                $crate::return_trace::Traced::capture(&mut self.$field, name, value)
+            }
+
+            fn capture_backtrace(&mut self) {
+                $crate::return_trace::Traced::capture_backtrace(&mut self.$field)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::return_trace::traceparent::TraceParent;
+    use crate::return_trace::{
+        IntoTraced, MissingValue, Ok, Result, TraceLog, Traced, TracedError,
+    };
+
+    struct Wrapped {
+        error: TracedError<MissingValue>,
+    }
+
+    delegate_traced!(Wrapped, error);
+
+    enum Layered {
+        Missing(TracedError<MissingValue>),
+        Wrapped(Wrapped, u32),
+    }
+
+    delegate_traced!(
+        enum Layered {
+            Missing,
+            Wrapped,
+        }
+    );
+
+    fn layered(wrap: bool) -> Result<(), Layered> {
+        if wrap {
+            Result::<(), _>::Err(Layered::Wrapped(wrapped().unwrap_err(), 3))?;
+        } else {
+            let error = MissingValue::of::<u32>().into_traced();
+            Result::<(), _>::Err(Layered::Missing(error))?;
+        }
+        Ok(())
+    }
+
+    fn pair() -> Result<(), (TracedError<MissingValue>, u32)> {
+        Result::<(), _>::Err((MissingValue::of::<u32>().into_traced(), 7))?;
+        Ok(())
+    }
+
+    fn wrapped() -> Result<(), Wrapped> {
+        let error = MissingValue::of::<u32>()
+            .into_traced()
+            .with_trace_parent(TraceParent::new(1, 2));
+        Result::<(), _>::Err(Wrapped { error })?;
+        Ok(())
+    }
+
+    #[test]
+    fn pairs_trace_through_their_error() {
+        let (error, context) = pair().unwrap_err();
+
+        assert_eq!(7, context);
+        assert_eq!(2, error.trace_log().len());
+    }
+
+    #[test]
+    fn delegated_structs_trace_through_their_field() {
+        let wrapped = wrapped().unwrap_err();
+
+        assert_eq!(2, wrapped.error.trace_log().len());
+        assert_eq!(Some(2), Traced::as_trace_log(&wrapped).map(TraceLog::len));
+        assert_eq!(
+            Some(&TraceParent::new(1, 2)),
+            Traced::trace_parent(&wrapped)
+        );
+    }
+
+    #[test]
+    fn delegated_enums_trace_through_their_variant() {
+        let missing = layered(false).unwrap_err();
+        assert_eq!(Some(2), Traced::as_trace_log(&missing).map(TraceLog::len));
+        assert_eq!(None, Traced::trace_parent(&missing));
+
+        let wrapped = layered(true).unwrap_err();
+        assert_eq!(Some(3), Traced::as_trace_log(&wrapped).map(TraceLog::len));
+        assert_eq!(
+            Some(&TraceParent::new(1, 2)),
+            Traced::trace_parent(&wrapped)
+        );
+    }
+}
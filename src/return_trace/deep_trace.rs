@@ -0,0 +1,153 @@
+//! Capturing a full `Backtrace` at selected propagation points.
+//!
+//! Frames only record where `?` was used, so a trace says nothing about the calls in between that
+//! returned the error some other way, or about how the program got to a `?` in the first place.
+//! Capturing a `Backtrace` at every `?` would answer that, but costs far too much to leave on.
+//! `deep_trace!` captures one only where it's asked for: wrapped around a traced `Result` right
+//! before its `?`, it attaches a backtrace to the frame recorded by that `?`, and the trace prints
+//! it under that frame.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::deep_trace;
+//! use trial_and_error::return_trace::{Ok, Result, ResultExt, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>().traced()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! fn load(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     // The path to here is worth knowing, so capture all of it
+//!     let value = deep_trace!(parse(input))?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let error = load("four").unwrap_err();
+//! let deep = error.trace_log().deep_frames();
+//!
+//! assert_eq!(1, deep.len());
+//! assert_eq!(2, deep[0].frame());
+//! ```
+
+use std::backtrace::Backtrace;
+use std::sync::Arc;
+
+use super::TraceLog;
+
+/// A `Backtrace` attached to a frame of a trace by `deep_trace!`.
+#[derive(Debug, Clone)]
+pub struct DeepFrame {
+    /// The index of the frame the backtrace is attached to.
+    frame: usize,
+    /// The captured backtrace, shared between clones of the trace.
+    backtrace: Arc<Backtrace>,
+}
+
+impl DeepFrame {
+    /// The index of the frame the backtrace is attached to, counting from the first location
+    /// recorded.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// The backtrace captured when the error was propagated through the frame.
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.backtrace
+    }
+}
+
+/// Backtraces can't be compared, so two `DeepFrame`s are equal if they are attached to the same
+/// frame and share the same captured backtrace, as clones of a trace do.
+impl PartialEq for DeepFrame {
+    fn eq(&self, other: &Self) -> bool {
+        self.frame == other.frame && Arc::ptr_eq(&self.backtrace, &other.backtrace)
+    }
+}
+
+impl Eq for DeepFrame {}
+
+impl TraceLog {
+    /// The backtraces captured with `deep_trace!`, oldest first.
+    pub fn deep_frames(&self) -> &[DeepFrame] {
+        self.deep_frames.as_deref().map_or(&[], Vec::as_slice)
+    }
+
+    /// The backtrace captured for the `index`th frame, if any.
+    pub fn backtrace_of(&self, index: usize) -> Option<&Backtrace> {
+        self.deep_frames()
+            .iter()
+            .find(|deep| deep.frame == index)
+            .map(DeepFrame::backtrace)
+    }
+
+    /// Attach `backtrace` to the next frame recorded.
+    pub fn push_backtrace(&mut self, backtrace: Backtrace) {
+        let frame = self.frames.len();
+        let deep_frames = self.deep_frames.get_or_insert_with(Default::default);
+
+        Arc::make_mut(deep_frames).push(DeepFrame {
+            frame,
+            backtrace: Arc::new(backtrace),
+        });
+        self.account();
+    }
+}
+
+/// Capture a full `Backtrace` if a traced `Result` is an error, attaching it to the next frame
+/// recorded, which is the one of the `?` right after the macro.
+///
+/// The backtrace is captured whether or not `RUST_BACKTRACE` is set. Only errors that are
+/// already `Traced` can keep it, and those whose trace isn't kept in a `TraceLog` drop it.
+#[macro_export]
+macro_rules! deep_trace {
+    ($result:expr) => {
+        $result.map_err(|mut error| {
+            $crate::return_trace::Traced::capture_backtrace(&mut error);
+            error
+        })
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::return_trace::{Err, IntoTraced, MissingValue, Ok, Result, TracedError};
+
+    fn missing() -> Result<(), TracedError<MissingValue>> {
+        Err(MissingValue::of::<u32>().into_traced())
+    }
+
+    fn shallow() -> Result<(), TracedError<MissingValue>> {
+        missing()?;
+        Ok(())
+    }
+
+    fn deep() -> Result<(), TracedError<MissingValue>> {
+        deep_trace!(shallow())?;
+        Ok(())
+    }
+
+    #[test]
+    fn backtraces_attach_to_the_next_frame() {
+        let error = deep().unwrap_err();
+        let trace = error.trace_log();
+
+        assert_eq!(3, trace.len());
+        assert_eq!(1, trace.deep_frames().len());
+        assert!(trace.backtrace_of(1).is_none());
+        assert!(trace.backtrace_of(2).is_some());
+        assert_eq!(trace, &trace.clone());
+    }
+
+    #[test]
+    fn backtraces_are_printed_under_their_frame() {
+        let printed = deep().unwrap_err().trace_log().to_string();
+        let lines: Vec<_> = printed.lines().collect();
+
+        assert!(lines[2].starts_with("   2: "));
+        assert_eq!("        backtrace:", lines[3]);
+        assert!(lines[4].starts_with("          "));
+    }
+}
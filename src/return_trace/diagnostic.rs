@@ -0,0 +1,206 @@
+//! Rendering traced errors in the layout rustc uses for diagnostics.
+//!
+//! Editors, terminals, and CI problem matchers already know how to pick source locations out of
+//! compiler output. `Diagnostic` prints a traced error the same way: the error message as an
+//! `error:` line pointing at the first frame of the trace, followed by a `note:` for each later
+//! frame, and the `source` chain as trailing `= note:` lines. When the source file can be read from
+//! the current directory, each location also shows its line with a caret under the column.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::diagnostic::Diagnostic;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let diagnostic = Diagnostic::new(&parse("four").unwrap_err()).to_string();
+//!
+//! assert!(diagnostic.starts_with("error: invalid digit found in string\n"));
+//! assert!(diagnostic.contains("--> "));
+//! ```
+
+use std::fmt;
+use std::fs;
+
+use super::visit::{Collector, Visit};
+use super::{CompactLocation, FrameKind};
+
+/// A traced error, displayed like a rustc diagnostic.
+pub struct Diagnostic {
+    error: Collector,
+}
+
+impl Diagnostic {
+    /// Create a new `Diagnostic` for a traced error.
+    pub fn new<E>(error: &E) -> Self
+    where
+        E: Visit + ?Sized,
+    {
+        Diagnostic {
+            error: Collector::collect(error),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let error = &self.error;
+        let width = error
+            .frames
+            .iter()
+            .map(|frame| frame.location().line().to_string().len())
+            .max()
+            .unwrap_or(1);
+
+        write!(f, "error: {}", error.message)?;
+
+        for (index, frame) in error.frames.iter().enumerate() {
+            if index > 0 {
+                match frame.kind() {
+                    FrameKind::Propagated => write!(f, "\nnote: propagated through here")?,
+                    FrameKind::Converted => write!(f, "\nnote: converted here")?,
+                    FrameKind::Sent => write!(
+                        f,
+                        "\nnote: sent across channel by thread {}",
+                        frame.thread().unwrap_or_default()
+                    )?,
+                }
+            }
+
+            write!(f, "\n{:width$}--> {}", "", frame.location(), width = width)?;
+            write_snippet(f, frame.location(), width)?;
+        }
+
+        if !error.sources.is_empty() {
+            write!(f, "\n{:width$} |", "", width = width)?;
+        }
+
+        for source in &error.sources {
+            write!(
+                f,
+                "\n{:width$} = note: caused by: {}",
+                "",
+                source,
+                width = width
+            )?;
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+impl fmt::Debug for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Write the line at `location` with a caret under its column, if the file can be read.
+///
+/// Locations without a column, like those of `Sent` frames, only show the line.
+fn write_snippet(
+    f: &mut fmt::Formatter<'_>,
+    location: CompactLocation,
+    width: usize,
+) -> fmt::Result {
+    let source = match fs::read_to_string(location.file()) {
+        std::result::Result::Ok(source) => source,
+        std::result::Result::Err(_) => return fmt::Result::Ok(()),
+    };
+    // Lines start at 1, so there is nothing to show for a frame at line 0
+    let text = match location
+        .line()
+        .checked_sub(1)
+        .and_then(|line| source.lines().nth(line as usize))
+    {
+        Some(text) => text,
+        None => return fmt::Result::Ok(()),
+    };
+
+    write!(f, "\n{:width$} |", "", width = width)?;
+    write!(f, "\n{:>width$} | {}", location.line(), text, width = width)?;
+
+    if location.column() == 0 {
+        return fmt::Result::Ok(());
+    }
+
+    // Keep tabs in the padding so the caret lines up however wide the terminal draws them
+    let padding: String = text
+        .chars()
+        .take(location.column() as usize - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    write!(f, "\n{:width$} | {}^", "", padding, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+
+    use crate::return_trace::storage::TraceStorage;
+    use crate::return_trace::{MissingValue, Ok, Result, TraceFrame, TracedError, TracedReport};
+
+    fn parse(input: &str) -> Result<u32, TracedError<std::num::ParseIntError>> {
+        let value = input.parse::<u32>()?;
+        Ok(value)
+    }
+
+    fn report(input: &str) -> Result<u32, TracedReport> {
+        let value = parse(input)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn points_at_first_frame_with_snippet() {
+        let error = parse("x").unwrap_err();
+        let location = error.trace_log().frames()[0].location();
+        let actual = Diagnostic::new(&error).to_string();
+
+        let line = location.line();
+        let width = line.to_string().len();
+        let expected = format!(
+            "error: invalid digit found in string\n\
+             {pad}--> {location}\n\
+             {pad} |\n\
+             {line} |         let value = input.parse::<u32>()?;\n\
+             {pad} |                     ^",
+            pad = " ".repeat(width),
+            location = location,
+            line = line,
+        );
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn later_frames_are_notes() {
+        let actual = Diagnostic::new(&report("x").unwrap_err()).to_string();
+        let lines: Vec<_> = actual.lines().collect();
+
+        let notes = lines.iter().filter(|line| line.starts_with("note: "));
+        assert_eq!(2, notes.count());
+        assert!(lines.contains(&"note: converted here"));
+        assert!(lines.contains(&"note: propagated through here"));
+        assert_eq!(3, actual.matches("--> src/return_trace/").count());
+    }
+
+    #[test]
+    fn frames_at_line_zero_have_no_snippet() {
+        let mut frame = TraceFrame::new(Location::caller());
+        frame.line = 0;
+
+        let mut error = TracedError::new(MissingValue::of::<u8>());
+        error.trace.push_frame(frame);
+        let actual = Diagnostic::new(&error).to_string();
+
+        assert!(actual.contains("--> src/return_trace/diagnostic.rs:0:"));
+        assert!(!actual.contains(" |"));
+    }
+}
@@ -0,0 +1,248 @@
+//! Structured fields that apply to a whole traced error.
+//!
+//! Values captured with `capture!` belong to one frame, which suits the locals of the function the
+//! frame is in, but not identifiers that hold for the whole request, like a tenant or request ID,
+//! which would otherwise have to be captured again at every frame. `set_field` attaches them to
+//! the error itself instead. Fields are typed, kept in the order they were first set, carried over
+//! into a `TracedReport`, and visited as metadata, so every exporter includes them.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::{IntoTraced, MissingValue, TracedReport};
+//!
+//! let mut error = MissingValue::of::<u32>().into_traced();
+//! error.set_field("tenant", "acme");
+//! error.set_field("attempt", 3);
+//!
+//! let report = TracedReport::new(error);
+//!
+//! assert_eq!(Some(&"acme".into()), report.field("tenant"));
+//! assert!(format!("{:?}", report).contains("attempt: 3"));
+//! ```
+
+use std::fmt;
+
+use super::{TracedError, TracedReport};
+
+/// The value of a field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// A boolean.
+    Bool(bool),
+    /// A signed integer.
+    I64(i64),
+    /// An unsigned integer.
+    U64(u64),
+    /// A floating point number.
+    F64(f64),
+    /// A string.
+    Str(String),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Bool(value) => fmt::Display::fmt(value, f),
+            FieldValue::I64(value) => fmt::Display::fmt(value, f),
+            FieldValue::U64(value) => fmt::Display::fmt(value, f),
+            FieldValue::F64(value) => fmt::Display::fmt(value, f),
+            FieldValue::Str(value) => f.write_str(value),
+        }
+    }
+}
+
+macro_rules! impl_from {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(impl From<$ty> for FieldValue {
+            fn from(value: $ty) -> Self {
+                FieldValue::$variant(value.into())
+            }
+        })*
+    };
+}
+
+impl_from!(
+    bool => Bool,
+    i8 => I64,
+    i16 => I64,
+    i32 => I64,
+    i64 => I64,
+    u8 => U64,
+    u16 => U64,
+    u32 => U64,
+    u64 => U64,
+    f32 => F64,
+    f64 => F64,
+    String => Str,
+    &str => Str,
+);
+
+impl From<usize> for FieldValue {
+    fn from(value: usize) -> Self {
+        FieldValue::U64(value as u64)
+    }
+}
+
+impl From<isize> for FieldValue {
+    fn from(value: isize) -> Self {
+        FieldValue::I64(value as i64)
+    }
+}
+
+/// The fields of a traced error, in the order they were first set.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fields {
+    entries: Vec<(&'static str, FieldValue)>,
+}
+
+/// Returned by the traced errors that don't have any fields.
+static NO_FIELDS: Fields = Fields {
+    entries: Vec::new(),
+};
+
+impl Fields {
+    /// The value of the field named `key`, if it's set.
+    pub fn get(&self, key: &str) -> Option<&FieldValue> {
+        self.entries
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Iterate over the fields, in the order they were first set.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &FieldValue)> + '_ {
+        self.entries.iter().map(|(name, value)| (*name, value))
+    }
+
+    /// The number of fields.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no fields are set.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Set the field named `key`, replacing its value if it's already set.
+    fn set(&mut self, key: &'static str, value: FieldValue) {
+        match self.entries.iter_mut().find(|(name, _)| *name == key) {
+            Some((_, old)) => *old = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+}
+
+/// Set a field in `fields`, which is only allocated once the first field is set.
+fn set(fields: &mut Option<Box<Fields>>, key: &'static str, value: FieldValue) {
+    fields.get_or_insert_with(Default::default).set(key, value);
+}
+
+impl<E, S> TracedError<E, S> {
+    /// Set the field named `key` for the whole error, replacing its value if it's already set.
+    ///
+    /// Fields named `message`, `type`, or `traceparent` are kept, but not visited, since those
+    /// names are taken by the error's own metadata.
+    pub fn set_field(&mut self, key: &'static str, value: impl Into<FieldValue>) {
+        set(&mut self.fields, key, value.into());
+    }
+
+    /// Set the field named `key` for the whole error; see `set_field`.
+    pub fn with_field(mut self, key: &'static str, value: impl Into<FieldValue>) -> Self {
+        self.set_field(key, value);
+        self
+    }
+
+    /// The value of the field named `key`, if it's set.
+    pub fn field(&self, key: &str) -> Option<&FieldValue> {
+        self.fields().get(key)
+    }
+
+    /// Every field of the error.
+    pub fn fields(&self) -> &Fields {
+        self.fields.as_deref().unwrap_or(&NO_FIELDS)
+    }
+}
+
+impl TracedReport {
+    /// Set the field named `key` for the whole error, replacing its value if it's already set.
+    ///
+    /// Fields named `message`, `type`, or `traceparent` are kept, but not visited, since those
+    /// names are taken by the error's own metadata.
+    pub fn set_field(&mut self, key: &'static str, value: impl Into<FieldValue>) {
+        set(&mut self.fields, key, value.into());
+    }
+
+    /// Set the field named `key` for the whole error; see `set_field`.
+    pub fn with_field(mut self, key: &'static str, value: impl Into<FieldValue>) -> Self {
+        self.set_field(key, value);
+        self
+    }
+
+    /// The value of the field named `key`, if it's set.
+    pub fn field(&self, key: &str) -> Option<&FieldValue> {
+        self.fields().get(key)
+    }
+
+    /// Every field of the error.
+    pub fn fields(&self) -> &Fields {
+        self.fields.as_deref().unwrap_or(&NO_FIELDS)
+    }
+}
+
+/// Whether a field named `key` would be mistaken for built-in metadata when visited.
+pub(crate) fn is_reserved(key: &str) -> bool {
+    matches!(key, "message" | "type" | "traceparent")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::visit::Collector;
+    use crate::return_trace::{IntoTraced, MissingValue, Ok, Result};
+
+    fn failing() -> Result<(), TracedReport> {
+        let error = MissingValue::of::<u32>()
+            .into_traced()
+            .with_field("tenant", "acme")
+            .with_field("request", 7u64);
+        Result::<(), _>::Err(error)?;
+        Ok(())
+    }
+
+    #[test]
+    fn fields_are_kept_in_order_and_replaced() {
+        let mut error = MissingValue::of::<u32>().into_traced();
+        assert!(error.fields().is_empty());
+
+        error.set_field("retry", true);
+        error.set_field("tenant", "acme");
+        error.set_field("retry", false);
+
+        assert_eq!(
+            vec![
+                ("retry", &FieldValue::Bool(false)),
+                ("tenant", &FieldValue::Str("acme".into()))
+            ],
+            error.fields().iter().collect::<Vec<_>>()
+        );
+        assert_eq!(None, error.field("missing"));
+    }
+
+    #[test]
+    fn fields_carry_into_reports_and_are_visited() {
+        let mut report = failing().unwrap_err();
+        report.set_field("message", "not visited");
+        assert_eq!(Some(&FieldValue::U64(7)), report.field("request"));
+
+        let collected = Collector::collect(&report);
+        assert_eq!("missing value of type `u32`", collected.message);
+        assert_eq!(
+            vec![
+                ("tenant".to_string(), "acme".to_string()),
+                ("request".to_string(), "7".to_string())
+            ],
+            collected.metadata
+        );
+    }
+}
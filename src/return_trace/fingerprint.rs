@@ -0,0 +1,172 @@
+//! Grouping recurring errors by the path they were propagated along.
+//!
+//! Error reporters that treat every occurrence of an error as unique drown the failures that
+//! matter in repeats of the ones that don't. `TraceLog::fingerprint` hashes the locations of a
+//! trace into a `Fingerprint`, which is the same for every error propagated along the same path,
+//! in every run of the same build. `TraceLog::fingerprint_with` can hash only the first frames
+//! instead, to group errors by where they were raised even if they went on to take different
+//! paths.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::fingerprint::FingerprintDepth;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! fn double(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = parse(input)?;
+//!
+//!     Ok(value * 2)
+//! }
+//!
+//! let first = parse("one").unwrap_err();
+//! let second = parse("two").unwrap_err();
+//! let doubled = double("three").unwrap_err();
+//!
+//! assert_eq!(first.trace_log().fingerprint(), second.trace_log().fingerprint());
+//! assert_ne!(first.trace_log().fingerprint(), doubled.trace_log().fingerprint());
+//!
+//! let origin = FingerprintDepth::First(1);
+//! assert_eq!(
+//!     first.trace_log().fingerprint_with(origin),
+//!     doubled.trace_log().fingerprint_with(origin)
+//! );
+//! ```
+
+use std::fmt;
+
+use super::storage::TraceStorage;
+use super::{FrameKind, TraceFrame, TraceLog, TracedError};
+
+/// FNV-1a offset basis; the std hashers aren't guaranteed to be stable between releases.
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+const PRIME: u64 = 0x0100_0000_01b3;
+
+/// A hash of the locations of a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    /// The hash as a number.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Formats the fingerprint as 16 hex digits.
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// How many frames of a trace go into its fingerprint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintDepth {
+    /// Every frame.
+    Full,
+    /// Only the first frames recorded, where the error was raised.
+    First(usize),
+}
+
+/// The fingerprint of `frames`, as deep as `depth`.
+pub(crate) fn fingerprint(frames: &[TraceFrame], depth: FingerprintDepth) -> Fingerprint {
+    let frames = match depth {
+        FingerprintDepth::Full => frames,
+        FingerprintDepth::First(count) => &frames[..count.min(frames.len())],
+    };
+
+    let mut hash = OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    };
+
+    for frame in frames {
+        let location = frame.location();
+        let kind: u8 = match frame.kind() {
+            FrameKind::Propagated => 0,
+            FrameKind::Converted => 1,
+            FrameKind::Sent => 2,
+        };
+
+        // The length keeps paths from running into the numbers that follow them
+        write(&(location.file().len() as u64).to_le_bytes());
+        write(location.file().as_bytes());
+        write(&location.line().to_le_bytes());
+        write(&location.column().to_le_bytes());
+        write(&[kind]);
+    }
+
+    Fingerprint(hash)
+}
+
+impl TraceLog {
+    /// A hash of every location in the trace, which is the same for every trace along the same
+    /// path in the same build.
+    pub fn fingerprint(&self) -> Fingerprint {
+        fingerprint(self.frames(), FingerprintDepth::Full)
+    }
+
+    /// A hash of the locations in the trace, as deep as `depth`.
+    pub fn fingerprint_with(&self, depth: FingerprintDepth) -> Fingerprint {
+        fingerprint(self.frames(), depth)
+    }
+}
+
+impl<E, S> TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    /// A hash of every location in the trace; see `TraceLog::fingerprint`.
+    pub fn fingerprint(&self) -> Fingerprint {
+        fingerprint(self.trace.frames(), FingerprintDepth::Full)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+
+    #[test]
+    fn fingerprints_depend_on_frames_only() {
+        let first = Location::caller();
+        let second = Location::caller();
+
+        let mut trace = TraceLog::new();
+        trace.push(first);
+        let mut other = TraceLog::new();
+        other.push(first);
+        assert_eq!(trace.fingerprint(), other.fingerprint());
+
+        trace.push(second);
+        other.push_converted(second);
+        assert_ne!(trace.fingerprint(), other.fingerprint());
+        assert_eq!(
+            trace.fingerprint_with(FingerprintDepth::First(1)),
+            other.fingerprint_with(FingerprintDepth::First(1))
+        );
+        assert_eq!(
+            trace.fingerprint(),
+            trace.fingerprint_with(FingerprintDepth::First(5))
+        );
+    }
+
+    #[test]
+    fn empty_trace_has_fixed_fingerprint() {
+        assert_eq!(
+            "cbf29ce484222325",
+            TraceLog::new().fingerprint().to_string()
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Future and stream combinators for traced results.
+//!
+//! The combinators in the `futures` crate's `TryFutureExt` and `TryStreamExt` are only available
+//! for futures and streams of `std::result::Result`. `TracedFutureExt` and `TracedStreamExt`
+//! provide the most common of them for futures and streams of traced `Result`s, so async
+//! pipelines don't have to convert back and forth to use them.
+//!
+//! ```rust
+//! use futures_executor::block_on;
+//! use futures_util::stream;
+//! use trial_and_error::return_trace::futures::{TracedFutureExt, TracedStreamExt};
+//! use trial_and_error::return_trace::{Ok, Result};
+//!
+//! async fn fetch(id: u32) -> Result<u32, String> {
+//!     Ok(id * 10)
+//! }
+//!
+//! let value = block_on(fetch(1).map_ok(|value| value + 1).and_then(fetch));
+//! assert_eq!(Some(110), value.ok());
+//!
+//! let values = stream::iter(vec![Ok(1), Ok(2), Ok(3)]).map_ok(|value: u32| value * 2);
+//! let values: Result<Vec<_>, String> = block_on(values.try_collect());
+//! assert_eq!(Some(vec![2, 4, 6]), values.ok());
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+use super::{Err, Ok, Result};
+
+/// Extension trait for futures that resolve to a traced `Result`.
+pub trait TracedFutureExt<T, E>: Future<Output = Result<T, E>> + Sized {
+    /// Map the success value of the future, leaving an error untouched.
+    fn map_ok<U, F>(self, op: F) -> MapOk<Self, F>
+    where
+        F: FnOnce(T) -> U,
+    {
+        MapOk {
+            future: self,
+            op: Some(op),
+        }
+    }
+
+    /// Map the error value of the future, leaving a success value untouched.
+    fn map_err<G, F>(self, op: F) -> MapErr<Self, F>
+    where
+        F: FnOnce(E) -> G,
+    {
+        MapErr {
+            future: self,
+            op: Some(op),
+        }
+    }
+
+    /// Chain a fallible future onto the success value of this one.
+    fn and_then<U, Fut, F>(self, op: F) -> AndThen<Self, Fut, F>
+    where
+        F: FnOnce(T) -> Fut,
+        Fut: Future<Output = Result<U, E>>,
+    {
+        AndThen {
+            state: AndThenState::First {
+                future: self,
+                op: Some(op),
+            },
+        }
+    }
+}
+
+impl<Fut, T, E> TracedFutureExt<T, E> for Fut where Fut: Future<Output = Result<T, E>> {}
+
+pin_project! {
+    /// Future for `TracedFutureExt::map_ok`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct MapOk<Fut, F> {
+        #[pin]
+        future: Fut,
+        op: Option<F>,
+    }
+}
+
+impl<Fut, F, T, U, E> Future for MapOk<Fut, F>
+where
+    Fut: Future<Output = Result<T, E>>,
+    F: FnOnce(T) -> U,
+{
+    type Output = Result<U, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = futures_core::ready!(this.future.poll(cx));
+        let op = this.op.take().expect("`MapOk` polled after completion");
+
+        Poll::Ready(result.map(op))
+    }
+}
+
+pin_project! {
+    /// Future for `TracedFutureExt::map_err`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct MapErr<Fut, F> {
+        #[pin]
+        future: Fut,
+        op: Option<F>,
+    }
+}
+
+impl<Fut, F, T, E, G> Future for MapErr<Fut, F>
+where
+    Fut: Future<Output = Result<T, E>>,
+    F: FnOnce(E) -> G,
+{
+    type Output = Result<T, G>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = futures_core::ready!(this.future.poll(cx));
+        let op = this.op.take().expect("`MapErr` polled after completion");
+
+        Poll::Ready(result.map_err(op))
+    }
+}
+
+pin_project! {
+    /// Future for `TracedFutureExt::and_then`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct AndThen<Fut1, Fut2, F> {
+        #[pin]
+        state: AndThenState<Fut1, Fut2, F>,
+    }
+}
+
+pin_project! {
+    #[project = AndThenStateProj]
+    enum AndThenState<Fut1, Fut2, F> {
+        First {
+            #[pin]
+            future: Fut1,
+            op: Option<F>,
+        },
+        Second {
+            #[pin]
+            future: Fut2,
+        },
+    }
+}
+
+impl<Fut1, Fut2, F, T, U, E> Future for AndThen<Fut1, Fut2, F>
+where
+    Fut1: Future<Output = Result<T, E>>,
+    Fut2: Future<Output = Result<U, E>>,
+    F: FnOnce(T) -> Fut2,
+{
+    type Output = Result<U, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        loop {
+            match this.state.as_mut().project() {
+                AndThenStateProj::First { future, op } => {
+                    match futures_core::ready!(future.poll(cx)) {
+                        Ok(value) => {
+                            let op = op.take().expect("`AndThen` polled after completion");
+                            let future = op(value);
+                            this.state.set(AndThenState::Second { future });
+                        }
+                        Err(error) => return Poll::Ready(Err(error)),
+                    }
+                }
+                AndThenStateProj::Second { future } => return future.poll(cx),
+            }
+        }
+    }
+}
+
+/// Extension trait for streams of traced `Result`s.
+pub trait TracedStreamExt<T, E>: Stream<Item = Result<T, E>> + Sized {
+    /// Map the success values of the stream, leaving errors untouched.
+    fn map_ok<U, F>(self, op: F) -> MapOkStream<Self, F>
+    where
+        F: FnMut(T) -> U,
+    {
+        MapOkStream { stream: self, op }
+    }
+
+    /// Map the error values of the stream, leaving success values untouched.
+    fn map_err<G, F>(self, op: F) -> MapErrStream<Self, F>
+    where
+        F: FnMut(E) -> G,
+    {
+        MapErrStream { stream: self, op }
+    }
+
+    /// Collect the success values of the stream, short-circuiting on the first error.
+    fn try_collect<C>(self) -> TryCollect<Self, C>
+    where
+        C: Default + Extend<T>,
+    {
+        TryCollect {
+            stream: self,
+            items: C::default(),
+        }
+    }
+}
+
+impl<St, T, E> TracedStreamExt<T, E> for St where St: Stream<Item = Result<T, E>> {}
+
+pin_project! {
+    /// Stream for `TracedStreamExt::map_ok`.
+    #[must_use = "streams do nothing unless polled"]
+    pub struct MapOkStream<St, F> {
+        #[pin]
+        stream: St,
+        op: F,
+    }
+}
+
+impl<St, F, T, U, E> Stream for MapOkStream<St, F>
+where
+    St: Stream<Item = Result<T, E>>,
+    F: FnMut(T) -> U,
+{
+    type Item = Result<U, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let op = this.op;
+        let item = futures_core::ready!(this.stream.poll_next(cx));
+
+        Poll::Ready(item.map(|result| result.map(op)))
+    }
+}
+
+pin_project! {
+    /// Stream for `TracedStreamExt::map_err`.
+    #[must_use = "streams do nothing unless polled"]
+    pub struct MapErrStream<St, F> {
+        #[pin]
+        stream: St,
+        op: F,
+    }
+}
+
+impl<St, F, T, E, G> Stream for MapErrStream<St, F>
+where
+    St: Stream<Item = Result<T, E>>,
+    F: FnMut(E) -> G,
+{
+    type Item = Result<T, G>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let op = this.op;
+        let item = futures_core::ready!(this.stream.poll_next(cx));
+
+        Poll::Ready(item.map(|result| result.map_err(op)))
+    }
+}
+
+pin_project! {
+    /// Future for `TracedStreamExt::try_collect`.
+    #[must_use = "futures do nothing unless you `.await` or poll them"]
+    pub struct TryCollect<St, C> {
+        #[pin]
+        stream: St,
+        items: C,
+    }
+}
+
+impl<St, C, T, E> Future for TryCollect<St, C>
+where
+    St: Stream<Item = Result<T, E>>,
+    C: Default + Extend<T>,
+{
+    type Output = Result<C, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+
+        loop {
+            match futures_core::ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(Ok(item)) => this.items.extend(Some(item)),
+                Some(Err(error)) => return Poll::Ready(Err(error)),
+                None => return Poll::Ready(Ok(std::mem::take(this.items))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures_executor::block_on;
+    use futures_util::stream;
+
+    async fn double(value: u32) -> Result<u32, String> {
+        Ok(value * 2)
+    }
+
+    async fn fail(value: u32) -> Result<u32, String> {
+        Err(format!("failed on {}", value))
+    }
+
+    #[test]
+    fn future_combinators_chain_success_values() {
+        let result = block_on(double(1).map_ok(|value| value + 1).and_then(double));
+
+        assert_eq!(Some(6), result.ok());
+    }
+
+    #[test]
+    fn future_combinators_short_circuit_on_error() {
+        let result = block_on(
+            fail(1)
+                .and_then(double)
+                .map_err(|error| format!("{}!", error)),
+        );
+
+        assert_eq!(Some(String::from("failed on 1!")), result.err());
+    }
+
+    #[test]
+    fn try_collect_stops_at_first_error() {
+        let items = vec![Ok(1), Err(String::from("second")), Ok(3)];
+        let result: Result<Vec<u32>, _> = block_on(
+            stream::iter(items)
+                .map_err(|error| error.to_uppercase())
+                .try_collect(),
+        );
+
+        assert_eq!(Some(String::from("SECOND")), result.err());
+    }
+}
@@ -0,0 +1,324 @@
+//! HTML rendering of traced errors.
+//!
+//! `fragment` renders a traced error as a self-contained `<div>` that can be embedded into an
+//! existing page, like a CI failure report or a debugging dashboard, while `page` wraps the same
+//! fragment into a standalone document with a small stylesheet. Each frame of the trace is a
+//! collapsible `<details>` element which, when the source file can be read from the current
+//! directory, contains the lines around the location with some basic syntax highlighting. The
+//! error's metadata is laid out in a table and its `source` chain is listed after the frames.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::{html, Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let html = html::fragment(&parse("four").unwrap_err());
+//!
+//! assert!(html.contains("<details"));
+//! ```
+
+use std::fmt::{self, Write};
+use std::fs;
+
+use super::visit::{Collector, Visit};
+use super::{FrameKind, TraceFrame};
+
+/// Lines of source shown before and after the location of each frame.
+const CONTEXT_LINES: usize = 2;
+
+const STYLESHEET: &str = "\
+.traced-error { font-family: sans-serif; }
+.traced-error table { border-collapse: collapse; margin-bottom: 1em; }
+.traced-error th, .traced-error td {
+    border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left;
+}
+.traced-error summary { cursor: pointer; font-family: monospace; }
+.traced-error .converted { color: #a0522d; }
+.traced-error pre { background: #f6f8fa; padding: 0.5em; }
+.traced-error mark { background: #fff3b0; display: inline-block; width: 100%; }
+.traced-error .kw { color: #a626a4; font-weight: bold; }
+.traced-error .str { color: #50a14f; }
+.traced-error .num { color: #986801; }
+.traced-error .comment { color: #a0a1a7; font-style: italic; }
+";
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+/// Render a traced error as an HTML fragment.
+pub fn fragment<E>(error: &E) -> String
+where
+    E: Visit + ?Sized,
+{
+    let mut html = String::new();
+    // Writing to a `String` can't fail
+    let _ = Collector::collect(error).write_fragment(&mut html);
+    html
+}
+
+/// Render a traced error as a standalone HTML page.
+pub fn page<E>(error: &E) -> String
+where
+    E: Visit + ?Sized,
+{
+    let mut html = String::new();
+    // Writing to a `String` can't fail
+    let _ = Collector::collect(error).write_page(&mut html);
+    html
+}
+
+impl Collector {
+    fn write_page(&self, out: &mut String) -> fmt::Result {
+        out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        writeln!(out, "<title>{}</title>", Escaped(&self.message))?;
+        writeln!(out, "<style>\n{}</style>", STYLESHEET)?;
+        out.push_str("</head>\n<body>\n");
+        self.write_fragment(out)?;
+        out.push_str("</body>\n</html>\n");
+        fmt::Result::Ok(())
+    }
+
+    fn write_fragment(&self, out: &mut String) -> fmt::Result {
+        out.push_str("<div class=\"traced-error\">\n");
+        writeln!(out, "<h2 class=\"message\">{}</h2>", Escaped(&self.message))?;
+
+        if !self.metadata.is_empty() {
+            out.push_str("<table class=\"metadata\">\n");
+            for (key, value) in &self.metadata {
+                writeln!(
+                    out,
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    Escaped(key),
+                    Escaped(value)
+                )?;
+            }
+            out.push_str("</table>\n");
+        }
+
+        if !self.frames.is_empty() {
+            out.push_str("<ol class=\"frames\" start=\"0\">\n");
+            for frame in &self.frames {
+                write_frame(out, frame)?;
+            }
+            out.push_str("</ol>\n");
+        }
+
+        if !self.sources.is_empty() {
+            out.push_str("<h3>Caused by</h3>\n<ol class=\"sources\">\n");
+            for source in &self.sources {
+                writeln!(out, "<li>{}</li>", Escaped(source))?;
+            }
+            out.push_str("</ol>\n");
+        }
+
+        out.push_str("</div>\n");
+        fmt::Result::Ok(())
+    }
+}
+
+fn write_frame(out: &mut String, frame: &TraceFrame) -> fmt::Result {
+    let location = frame.location();
+    let class = match frame.kind() {
+        FrameKind::Propagated => "propagated",
+        FrameKind::Converted => "converted",
+        FrameKind::Sent => "sent",
+    };
+
+    writeln!(
+        out,
+        "<li class=\"{}\"><details><summary>{}</summary>",
+        class,
+        Escaped(&frame.to_string())
+    )?;
+
+    if let std::result::Result::Ok(source) = fs::read_to_string(location.file()) {
+        let line = location.line() as usize;
+        let first = line.saturating_sub(CONTEXT_LINES).max(1);
+
+        out.push_str("<pre><code class=\"language-rust\">");
+        for (number, text) in source.lines().enumerate().skip(first - 1) {
+            let number = number + 1;
+            if number > line + CONTEXT_LINES {
+                break;
+            }
+
+            if number == line {
+                out.push_str("<mark>");
+            }
+            write!(out, "{:>5} | ", number)?;
+            highlight(out, text);
+            if number == line {
+                out.push_str("</mark>");
+            }
+            out.push('\n');
+        }
+        out.push_str("</code></pre>");
+    }
+
+    out.push_str("</details></li>\n");
+    fmt::Result::Ok(())
+}
+
+/// Append a line of Rust source to `out`, escaped, with keywords, literals, and comments wrapped in
+/// `<span>`s.
+///
+/// This only looks at one line at a time, so it's not accurate for things like multi-line strings,
+/// but it's good enough to make a snippet easier to read.
+fn highlight(out: &mut String, line: &str) {
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        let len = if rest.starts_with("//") {
+            rest.len()
+        } else if c == '"' {
+            string_literal_len(rest)
+        } else if c.is_alphanumeric() || c == '_' {
+            rest.find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or_else(|| rest.len())
+        } else {
+            c.len_utf8()
+        };
+
+        let (token, remainder) = rest.split_at(len);
+        let class = if token.starts_with("//") {
+            Some("comment")
+        } else if token.starts_with('"') {
+            Some("str")
+        } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+            Some("num")
+        } else if KEYWORDS.contains(&token) {
+            Some("kw")
+        } else {
+            None
+        };
+
+        match class {
+            Some(class) => {
+                let _ = write!(out, "<span class=\"{}\">{}</span>", class, Escaped(token));
+            }
+            None => {
+                let _ = write!(out, "{}", Escaped(token));
+            }
+        }
+
+        rest = remainder;
+    }
+}
+
+/// The length of the string literal at the start of `input`, including both quotes if the closing
+/// one is on the same line.
+fn string_literal_len(input: &str) -> usize {
+    let mut escaped = false;
+
+    for (index, c) in input.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return index + 1,
+            _ => {}
+        }
+    }
+
+    input.len()
+}
+
+/// Displays a string with the characters that are special in HTML escaped.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => f.write_char(c)?,
+            }
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::error::Error;
+
+    use crate::return_trace::{Ok, Result, TracedError, TracedReport};
+
+    #[derive(Debug)]
+    struct Tagged;
+
+    impl fmt::Display for Tagged {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("<script> & friends")
+        }
+    }
+
+    impl Error for Tagged {}
+
+    fn fail() -> Result<(), TracedError<Tagged>> {
+        let error: std::result::Result<(), Tagged> = std::result::Result::Err(Tagged);
+        error?;
+        Ok(())
+    }
+
+    fn outer() -> Result<(), TracedReport> {
+        fail()?;
+        Ok(())
+    }
+
+    #[test]
+    fn fragment_escapes_and_lists_frames() {
+        let html = fragment(&outer().unwrap_err());
+
+        assert!(html.starts_with("<div class=\"traced-error\">"));
+        assert!(html.contains("&lt;script&gt; &amp; friends"));
+        assert!(!html.contains("<script>"));
+        assert_eq!(3, html.matches("<details>").count());
+        assert!(html.contains("<li class=\"converted\">"));
+    }
+
+    #[test]
+    fn frames_include_highlighted_snippet() {
+        let html = fragment(&fail().unwrap_err());
+
+        assert!(html.contains("<mark>"));
+        assert!(html.contains("<span class=\"kw\">let</span>"));
+    }
+
+    #[test]
+    fn page_wraps_fragment() {
+        let error = fail().unwrap_err();
+        let html = page(&error);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>&lt;script&gt; &amp; friends</title>"));
+        assert!(html.contains(&fragment(&error)));
+    }
+
+    #[test]
+    fn highlights_tokens() {
+        let mut out = String::new();
+        highlight(&mut out, r#"let x = "a\"b" + 1; // done"#);
+
+        assert_eq!(
+            "<span class=\"kw\">let</span> x = <span class=\"str\">&quot;a\\&quot;b&quot;</span> + \
+             <span class=\"num\">1</span>; <span class=\"comment\">// done</span>",
+            out
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Clickable source locations in terminal output.
+//!
+//! Many terminals support OSC 8 escape sequences, which turn a piece of text into a hyperlink.
+//! Once enabled with `set_hyperlinks`, every frame printed by the `Display` impl of `TraceLog`,
+//! which is also what `TracedReport` uses for its `Debug` output, links to its source location, so
+//! a trace printed to the terminal can be followed straight into an editor.
+//!
+//! `Location::file` is usually relative to the directory the crate was built from, so relative
+//! paths are resolved against the current directory when the link is written.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::hyperlink::{self, Hyperlinks, LinkScheme};
+//!
+//! // Open frames in VS Code if the terminal looks like it can handle hyperlinks
+//! hyperlink::set_hyperlinks(Hyperlinks::Auto, LinkScheme::Vscode);
+//! ```
+
+use std::env;
+use std::fmt::{self, Write};
+use std::lazy::SyncLazy;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use super::CompactLocation;
+
+/// When to emit hyperlinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hyperlinks {
+    /// Never emit hyperlinks.
+    Never,
+    /// Emit hyperlinks if the environment looks like a terminal that supports them.
+    ///
+    /// This only looks at environment variables, like `TERM_PROGRAM` and `VTE_VERSION`, and
+    /// can't tell whether the output is actually going to a terminal. `FORCE_HYPERLINK=1` or
+    /// `FORCE_HYPERLINK=0` override the guess.
+    Auto,
+    /// Always emit hyperlinks.
+    Always,
+}
+
+/// What a hyperlink to a source location points at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkScheme {
+    /// A `file://` URL, opened by whatever the terminal considers the default application.
+    File,
+    /// A `vscode://file/` URL that opens the file at the line and column in VS Code.
+    Vscode,
+    /// An `idea://open` URL that opens the file at the line in a JetBrains IDE.
+    Idea,
+    /// A URL template in which `{path}`, `{line}`, and `{column}` are replaced with the absolute
+    /// path of the file and the position in it.
+    Custom(String),
+}
+
+// Checked before taking the lock so that printing traces without hyperlinks stays cheap
+static HYPERLINKS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+static LINK_SCHEME: SyncLazy<RwLock<LinkScheme>> = SyncLazy::new(|| RwLock::new(LinkScheme::File));
+
+/// Set when printed frames link to their source, and what they link to.
+pub fn set_hyperlinks(when: Hyperlinks, scheme: LinkScheme) {
+    let enabled = match when {
+        Hyperlinks::Never => false,
+        Hyperlinks::Auto => terminal_supports_hyperlinks(),
+        Hyperlinks::Always => true,
+    };
+
+    *LINK_SCHEME.write().unwrap_or_else(|e| e.into_inner()) = scheme;
+    HYPERLINKS_ENABLED.store(enabled, Ordering::Release);
+}
+
+/// Guess whether the terminal supports OSC 8 from the environment.
+fn terminal_supports_hyperlinks() -> bool {
+    if let std::result::Result::Ok(force) = env::var("FORCE_HYPERLINK") {
+        return force != "0";
+    }
+
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+    let vte_version = env::var("VTE_VERSION")
+        .ok()
+        .and_then(|version| version.parse::<u32>().ok())
+        .unwrap_or(0);
+
+    matches!(
+        term_program.as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "Hyper"
+    ) || term == "xterm-kitty"
+        || env::var_os("WT_SESSION").is_some()
+        || env::var_os("KONSOLE_VERSION").is_some()
+        || vte_version >= 5000
+}
+
+/// The URL that `scheme` uses to link to `location`, with `file` as its absolute path.
+///
+/// The built-in schemes percent-encode the path, so files with spaces or `&` in their names still
+/// make valid URLs. Custom templates get the path as is.
+fn link(scheme: &LinkScheme, file: &Path, location: CompactLocation) -> String {
+    let path = percent_encode(&file.to_string_lossy());
+
+    match scheme {
+        LinkScheme::File => format!("file://{}", path),
+        LinkScheme::Vscode => format!(
+            "vscode://file/{}:{}:{}",
+            path,
+            location.line(),
+            location.column()
+        ),
+        LinkScheme::Idea => format!("idea://open?file={}&line={}", path, location.line()),
+        LinkScheme::Custom(template) => template
+            .replace("{path}", &file.display().to_string())
+            .replace("{line}", &location.line().to_string())
+            .replace("{column}", &location.column().to_string()),
+    }
+}
+
+/// Percent-encode every byte of `path` that isn't allowed as is in the path of a URL.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                // Writing to a `String` can't fail
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+/// Write `text` for a frame at `location`, wrapped in a hyperlink if they are enabled.
+pub(crate) fn write_linked(
+    f: &mut fmt::Formatter<'_>,
+    location: CompactLocation,
+    text: &dyn fmt::Display,
+) -> fmt::Result {
+    if !HYPERLINKS_ENABLED.load(Ordering::Acquire) {
+        return write!(f, "{}", text);
+    }
+
+    let scheme = LINK_SCHEME.read().unwrap_or_else(|e| e.into_inner());
+    write_hyperlink(f, &scheme, location, text)
+}
+
+/// Write `text` wrapped in an OSC 8 hyperlink to `location`.
+fn write_hyperlink(
+    f: &mut fmt::Formatter<'_>,
+    scheme: &LinkScheme,
+    location: CompactLocation,
+    text: &dyn fmt::Display,
+) -> fmt::Result {
+    let mut file = PathBuf::from(location.file());
+    if file.is_relative() {
+        if let std::result::Result::Ok(dir) = env::current_dir() {
+            file = dir.join(file);
+        }
+    }
+
+    write!(
+        f,
+        "\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\",
+        link(scheme, &file, location),
+        text
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+
+    use crate::return_trace::TraceLog;
+
+    #[test]
+    fn schemes_build_links() {
+        let location = CompactLocation::from(Location::caller());
+        let file = Path::new("/repo/src/lib.rs");
+        let line = location.line();
+        let column = location.column();
+
+        assert_eq!(
+            "file:///repo/src/lib.rs",
+            link(&LinkScheme::File, file, location)
+        );
+        assert_eq!(
+            format!("vscode://file//repo/src/lib.rs:{}:{}", line, column),
+            link(&LinkScheme::Vscode, file, location)
+        );
+        assert_eq!(
+            format!("idea://open?file=/repo/src/lib.rs&line={}", line),
+            link(&LinkScheme::Idea, file, location)
+        );
+        assert_eq!(
+            format!("edit:/repo/src/lib.rs@{}", line),
+            link(
+                &LinkScheme::Custom("edit:{path}@{line}".into()),
+                file,
+                location
+            )
+        );
+    }
+
+    #[test]
+    fn links_percent_encode_paths() {
+        let location = CompactLocation::from(Location::caller());
+        let file = Path::new("/my repo/src/a&b.rs");
+        let line = location.line();
+
+        assert_eq!(
+            "file:///my%20repo/src/a%26b.rs",
+            link(&LinkScheme::File, file, location)
+        );
+        assert_eq!(
+            format!("idea://open?file=/my%20repo/src/a%26b.rs&line={}", line),
+            link(&LinkScheme::Idea, file, location)
+        );
+        assert!(link(&LinkScheme::Vscode, file, location)
+            .starts_with("vscode://file//my%20repo/src/a%26b.rs:"));
+    }
+
+    #[test]
+    fn hyperlinks_wrap_text_in_osc_8() {
+        struct Linked(CompactLocation);
+
+        impl fmt::Display for Linked {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write_hyperlink(f, &LinkScheme::File, self.0, &"here")
+            }
+        }
+
+        let linked = Linked(Location::caller().into()).to_string();
+        let dir = env::current_dir().unwrap();
+        let dir = percent_encode(&dir.to_string_lossy());
+        let start = format!("\x1b]8;;file://{}/src/", dir);
+
+        assert!(linked.starts_with(&start), "{:?}", linked);
+        assert!(linked.ends_with("\x1b\\here\x1b]8;;\x1b\\"));
+    }
+
+    #[test]
+    fn disabled_hyperlinks_leave_trace_untouched() {
+        let mut trace = TraceLog::new();
+        trace.push(Location::caller());
+
+        assert!(!trace.to_string().contains('\x1b'));
+    }
+}
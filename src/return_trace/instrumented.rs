@@ -0,0 +1,152 @@
+//! Success-path tracing.
+//!
+//! Error return traces only show the path taken by failures. When trying to understand the control
+//! flow of a complex pipeline, it can be just as useful to know which `?`s a successful value
+//! flowed through. Wrapping a value in `Instrumented` opts it into tracing: every time a
+//! `Result::Ok(Instrumented<T>)` passes through a `?`, the location of the `?` is recorded in the
+//! value's own `TraceLog`.
+//!
+//! This requires the `instrumented` feature, since it adds a small cost to the success path of
+//! every `?`, even for values that aren't instrumented.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::instrumented::Instrumented;
+//! use trial_and_error::return_trace::{Ok, Result};
+//!
+//! fn load() -> Result<Instrumented<u32>, ()> {
+//!     Ok(Instrumented::new(4))
+//! }
+//!
+//! fn pipeline() -> Result<Instrumented<u32>, ()> {
+//!     let value = load()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! fn run() -> Result<u32, ()> {
+//!     let value = pipeline()?;
+//!
+//!     assert_eq!(2, value.trace_log().len());
+//!
+//!     Ok(value.into_inner())
+//! }
+//!
+//! assert_eq!(Some(4), run().ok());
+//! ```
+
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+
+use super::TraceLog;
+
+/// Wrapper for a success value that records the locations it is propagated through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instrumented<T> {
+    /// The wrapped value.
+    value: T,
+    /// The locations the value was propagated through.
+    trace: TraceLog,
+}
+
+impl<T> Instrumented<T> {
+    /// Create a new `Instrumented` with an empty trace from an input value.
+    pub fn new(value: T) -> Self {
+        Instrumented {
+            value,
+            trace: TraceLog::new(),
+        }
+    }
+
+    /// Unwrap the `Instrumented`, discarding its trace.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// The locations the value was propagated through.
+    pub fn trace_log(&self) -> &TraceLog {
+        &self.trace
+    }
+}
+
+impl<T> From<T> for Instrumented<T> {
+    fn from(value: T) -> Self {
+        Instrumented::new(value)
+    }
+}
+
+impl<T> Deref for Instrumented<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Instrumented<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+/// Helper trait used to only record success-path locations for `Instrumented` values.
+pub(super) trait MaybeInstrument: Sized {
+    fn maybe_instrument(self, location: &'static Location<'static>) -> Self;
+}
+
+impl<T> MaybeInstrument for T {
+    #[inline]
+    default fn maybe_instrument(self, _location: &'static Location<'static>) -> Self {
+        self
+    }
+}
+
+impl<T> MaybeInstrument for Instrumented<T> {
+    fn maybe_instrument(mut self, location: &'static Location<'static>) -> Self {
+        if !super::is_denylisted(location) {
+            self.trace.push(location);
+        }
+
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Err, Ok, Result};
+
+    fn source(fail: bool) -> Result<Instrumented<u32>, &'static str> {
+        if fail {
+            Err("failed")
+        } else {
+            Ok(Instrumented::new(1))
+        }
+    }
+
+    fn middle(fail: bool) -> Result<Instrumented<u32>, &'static str> {
+        let mut value = source(fail)?;
+        *value += 1;
+        Ok(value)
+    }
+
+    #[test]
+    fn ok_values_record_each_question_mark() {
+        let value = middle(false).unwrap();
+
+        assert_eq!(2, *value);
+        assert_eq!(1, value.trace_log().len());
+        assert_eq!(file!(), value.trace_log().frames()[0].location().file());
+    }
+
+    #[test]
+    fn uninstrumented_values_are_untouched() {
+        fn plain() -> Result<u32, ()> {
+            let value = Result::<u32, ()>::Ok(1)?;
+            Ok(value)
+        }
+
+        assert_eq!(Some(1), plain().ok());
+        assert_eq!(Some("failed"), middle(true).err());
+    }
+}
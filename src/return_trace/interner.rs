@@ -0,0 +1,241 @@
+//! Global table of the file paths that appear in traces.
+//!
+//! Every frame of a trace used to keep a pointer to its `Location`, which on 64-bit targets is as
+//! big as the rest of the frame put together. Since a program only ever has a fixed set of source
+//! files, frames instead keep a `u32` index into this table, which is filled in the first time a
+//! path is recorded.
+//!
+//! Paths of sources other than Rust, recorded through `LocationLike`, are copied into the table
+//! the first time they are seen.
+//!
+//! Paths can't be removed from the table while frames might still refer to them, so it only ever
+//! grows. For long-running services, `stats` reports how much memory it holds, `shrink` releases
+//! spare capacity, and `set_max_paths` caps the number of paths, past which frames in new files
+//! are reported as being in `<unknown>`.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::interner;
+//!
+//! let stats = interner::stats();
+//! println!("{} paths using {} bytes", stats.paths, stats.table_bytes);
+//!
+//! for path in interner::paths() {
+//!     println!("{}", path);
+//! }
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::lazy::SyncLazy;
+use std::mem;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+/// Index used for frames in files that didn't fit in the table.
+const OVERFLOW_ID: u32 = u32::MAX;
+
+/// Path reported for frames in files that didn't fit in the table.
+const OVERFLOW_PATH: &str = "<unknown>";
+
+#[derive(Default)]
+struct Interner {
+    /// The index of every interned path.
+    ids: HashMap<&'static str, u32>,
+    /// The interned paths, by index.
+    paths: Vec<&'static str>,
+}
+
+static INTERNER: SyncLazy<RwLock<Interner>> = SyncLazy::new(|| RwLock::new(Interner::default()));
+
+static MAX_PATHS: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+static OVERFLOWED: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // Paths recorded by a thread, keyed by address, so the common case of seeing a path again
+    // neither takes the lock nor hashes the whole path
+    static CACHE: RefCell<HashMap<(usize, usize), u32>> = RefCell::new(HashMap::new());
+
+    // Consecutive frames are often in the same file, so the last path seen is checked first
+    static LAST: Cell<Option<((usize, usize), u32)>> = Cell::new(None);
+}
+
+/// The index of `path` in the table, adding it if it hasn't been seen before.
+pub(crate) fn intern(path: &'static str) -> u32 {
+    let key = (path.as_ptr() as usize, path.len());
+
+    if let Some((last, id)) = LAST.with(Cell::get) {
+        if last == key {
+            return id;
+        }
+    }
+
+    let id = CACHE.with(|cache| {
+        if let Some(&id) = cache.borrow().get(&key) {
+            return id;
+        }
+
+        let id = intern_shared(path);
+
+        // Overflowed paths aren't cached, so they make it into the table if the limit is raised
+        if id != OVERFLOW_ID {
+            cache.borrow_mut().insert(key, id);
+        }
+        id
+    });
+
+    LAST.with(|last| last.set(Some((key, id))));
+    id
+}
+
+/// The index of a path that may not live for `'static`, like the path of a script, copying it
+/// into the table if it hasn't been seen before.
+pub(crate) fn intern_copied(path: &str) -> u32 {
+    {
+        let interner = INTERNER.read().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(&id) = interner.ids.get(path) {
+            return id;
+        }
+
+        // Checked before copying, so a full table doesn't leak a copy of every path it turns away
+        if interner.paths.len() >= MAX_PATHS.load(Ordering::Relaxed) {
+            OVERFLOWED.fetch_add(1, Ordering::Relaxed);
+            return OVERFLOW_ID;
+        }
+    }
+
+    // Paths are never removed from the table, so the copy has to live as long as the program. If
+    // another thread copies the same path first, this copy is the only one that is wasted.
+    intern_shared(Box::leak(Box::from(path)))
+}
+
+/// The index of `path` in the global table, adding it if it hasn't been seen before.
+fn intern_shared(path: &'static str) -> u32 {
+    if let Some(&id) = INTERNER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .ids
+        .get(path)
+    {
+        return id;
+    }
+
+    let mut interner = INTERNER.write().unwrap_or_else(|e| e.into_inner());
+
+    // Another thread may have interned the path between releasing the read lock and taking the
+    // write lock
+    if let Some(&id) = interner.ids.get(path) {
+        return id;
+    }
+
+    if interner.paths.len() >= MAX_PATHS.load(Ordering::Relaxed) {
+        OVERFLOWED.fetch_add(1, Ordering::Relaxed);
+        return OVERFLOW_ID;
+    }
+
+    let id = match u32::try_from(interner.paths.len()) {
+        std::result::Result::Ok(id) if id != OVERFLOW_ID => id,
+        _ => return OVERFLOW_ID,
+    };
+    interner.paths.push(path);
+    interner.ids.insert(path, id);
+    id
+}
+
+/// The path interned at `id`.
+pub(crate) fn resolve(id: u32) -> &'static str {
+    if id == OVERFLOW_ID {
+        return OVERFLOW_PATH;
+    }
+
+    INTERNER.read().unwrap_or_else(|e| e.into_inner()).paths[id as usize]
+}
+
+/// A snapshot of the size of the path table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InternerStats {
+    /// The number of interned paths.
+    pub paths: usize,
+    /// The combined length of the interned paths. The paths of Rust sources are `'static`
+    /// strings that the table only points to, while other paths were copied into it.
+    pub path_bytes: usize,
+    /// An estimate of the memory allocated for the table itself.
+    pub table_bytes: usize,
+    /// How many times a path wasn't interned because the table was full.
+    pub overflowed: u64,
+}
+
+/// A snapshot of the size of the path table.
+pub fn stats() -> InternerStats {
+    let interner = INTERNER.read().unwrap_or_else(|e| e.into_inner());
+
+    // Each hash table slot also has a byte of control data
+    let slot = mem::size_of::<(&'static str, u32)>() + 1;
+
+    InternerStats {
+        paths: interner.paths.len(),
+        path_bytes: interner.paths.iter().map(|path| path.len()).sum(),
+        table_bytes: interner.ids.capacity() * slot
+            + interner.paths.capacity() * mem::size_of::<&'static str>(),
+        overflowed: OVERFLOWED.load(Ordering::Relaxed),
+    }
+}
+
+/// Every interned path, in the order they were first recorded.
+pub fn paths() -> Vec<&'static str> {
+    INTERNER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .paths
+        .clone()
+}
+
+/// Release capacity the table allocated but isn't using, along with the current thread's cache.
+pub fn shrink() {
+    let mut interner = INTERNER.write().unwrap_or_else(|e| e.into_inner());
+    interner.ids.shrink_to_fit();
+    interner.paths.shrink_to_fit();
+
+    CACHE.with(|cache| cache.borrow_mut().shrink_to_fit());
+}
+
+/// Stop interning new paths once the table holds `max` of them, or never if `max` is `None`.
+///
+/// Frames in files that aren't interned report `<unknown>` as their file.
+pub fn set_max_paths(max: Option<usize>) {
+    MAX_PATHS.store(max.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning_is_idempotent() {
+        let first = intern("src/interned/first.rs");
+        let second = intern("src/interned/second.rs");
+
+        assert_ne!(first, second);
+        assert_eq!(first, intern("src/interned/first.rs"));
+        assert_eq!("src/interned/second.rs", resolve(second));
+    }
+
+    #[test]
+    fn stats_and_paths_cover_interned_paths() {
+        intern("src/interned/stats.rs");
+        shrink();
+
+        let stats = stats();
+        assert!(stats.paths >= 1);
+        assert!(stats.path_bytes >= "src/interned/stats.rs".len());
+        assert!(stats.table_bytes > 0);
+        assert!(paths().contains(&"src/interned/stats.rs"));
+    }
+
+    #[test]
+    fn full_table_reports_unknown_path() {
+        assert_eq!(OVERFLOW_PATH, resolve(OVERFLOW_ID));
+    }
+}
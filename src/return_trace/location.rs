@@ -0,0 +1,239 @@
+//! Recording positions in sources other than Rust.
+//!
+//! Script interpreters, config loaders, and template engines fail on behalf of a file the user
+//! wrote, and the line of that file is usually more useful than the Rust function that noticed.
+//! Anything that implements `LocationLike` can be recorded as a frame with `record_at`, so
+//! positions in the user's source end up in the same trace as the `?`s of the Rust code around
+//! them, and are shown, exported, and fingerprinted like any other frame.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::location::SourcePosition;
+//! use trial_and_error::return_trace::{IntoTraced, MissingValue};
+//!
+//! let path = format!("{}.toml", "deploy");
+//!
+//! let mut error = MissingValue::of::<u32>().into_traced();
+//! error.record_at(&SourcePosition::new(&path, 12, 5));
+//!
+//! let frames = error.trace_log().frames();
+//! assert_eq!("deploy.toml:12:5", frames[1].to_string());
+//! ```
+
+use std::convert::TryFrom;
+use std::panic::Location;
+
+use super::storage::TraceStorage;
+use super::{
+    interner, is_denylisted, trace_mode, CompactLocation, FrameKind, TraceFrame, TraceLog,
+    TraceMode, TracedError, TracedReport,
+};
+
+/// A position in a source file that a frame can be recorded at.
+pub trait LocationLike {
+    /// The path of the source file.
+    fn file(&self) -> &str;
+
+    /// The line number, starting at 1.
+    fn line(&self) -> u32;
+
+    /// The column number, starting at 1, or 0 if it isn't known.
+    fn column(&self) -> u32;
+}
+
+impl LocationLike for Location<'_> {
+    fn file(&self) -> &str {
+        Location::file(self)
+    }
+
+    fn line(&self) -> u32 {
+        Location::line(self)
+    }
+
+    fn column(&self) -> u32 {
+        Location::column(self)
+    }
+}
+
+impl LocationLike for CompactLocation {
+    fn file(&self) -> &str {
+        CompactLocation::file(self)
+    }
+
+    fn line(&self) -> u32 {
+        CompactLocation::line(self)
+    }
+
+    fn column(&self) -> u32 {
+        CompactLocation::column(self)
+    }
+}
+
+/// A position in a source that isn't Rust, like the script an interpreter was running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SourcePosition<'a> {
+    file: &'a str,
+    line: u32,
+    column: u32,
+}
+
+impl<'a> SourcePosition<'a> {
+    /// Create a new `SourcePosition` at `line` and `column` of `file`.
+    ///
+    /// Lines start at 1, like those of `Location`, so positions from sources that count from 0
+    /// need to be shifted first. Columns start at 1 too, or are 0 if they aren't known.
+    pub fn new(file: &'a str, line: u32, column: u32) -> Self {
+        debug_assert!(line > 0, "lines of a `SourcePosition` start at 1");
+
+        SourcePosition { file, line, column }
+    }
+}
+
+impl LocationLike for SourcePosition<'_> {
+    fn file(&self) -> &str {
+        self.file
+    }
+
+    fn line(&self) -> u32 {
+        self.line
+    }
+
+    fn column(&self) -> u32 {
+        self.column
+    }
+}
+
+impl CompactLocation {
+    /// Store any `LocationLike` compactly, copying its path into the interner if it hasn't been
+    /// seen before.
+    pub fn of<L>(location: &L) -> Self
+    where
+        L: LocationLike + ?Sized,
+    {
+        CompactLocation {
+            file: interner::intern_copied(location.file()),
+            line: location.line(),
+            column: u16::try_from(location.column()).unwrap_or(u16::MAX),
+        }
+    }
+}
+
+impl TraceFrame {
+    /// Create a new `TraceFrame` for an error propagated through any `LocationLike`.
+    pub fn at<L>(location: &L) -> Self
+    where
+        L: LocationLike + ?Sized,
+    {
+        let CompactLocation { file, line, column } = CompactLocation::of(location);
+
+        TraceFrame {
+            file,
+            line,
+            column,
+            kind: FrameKind::Propagated,
+        }
+    }
+}
+
+impl TraceLog {
+    /// Record a position in any source at the end of the log.
+    pub fn push_at<L>(&mut self, location: &L)
+    where
+        L: LocationLike + ?Sized,
+    {
+        self.push_frame(TraceFrame::at(location));
+    }
+}
+
+impl<E, S> TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    /// Record that the error was propagated through a position in some source, like the line of
+    /// a script that was running when it happened.
+    ///
+    /// Like the frames recorded by `?`, the position is dropped if it's on the frame denylist,
+    /// and isn't recorded at all in `TraceMode::Counters`.
+    pub fn record_at<L>(&mut self, location: &L)
+    where
+        L: LocationLike + ?Sized,
+    {
+        record_position(&mut self.trace, location);
+    }
+}
+
+impl TracedReport {
+    /// Record that the error was propagated through a position in some source; see
+    /// `TracedError::record_at`.
+    pub fn record_at<L>(&mut self, location: &L)
+    where
+        L: LocationLike + ?Sized,
+    {
+        record_position(&mut self.trace, location);
+    }
+}
+
+/// Record a position in some source, unless tracing is disabled for it.
+fn record_position<S, L>(trace: &mut S, location: &L)
+where
+    S: TraceStorage,
+    L: LocationLike + ?Sized,
+{
+    if !is_denylisted(location) && trace_mode() == TraceMode::Full {
+        trace.push_frame(TraceFrame::at(location));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Err, Ok, Result};
+
+    /// Run a "script" that fails on its second line.
+    fn run(script: &str) -> Result<(), TracedReport> {
+        for (index, line) in script.lines().enumerate() {
+            if let Err(mut error) = eval(line) {
+                let line = u32::try_from(index + 1).unwrap_or(u32::MAX);
+                error.record_at(&SourcePosition::new("scripts/init.lua", line, 0));
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn eval(line: &str) -> Result<(), TracedReport> {
+        line.parse::<u8>()?;
+        Ok(())
+    }
+
+    #[test]
+    fn script_positions_join_the_trace() {
+        let report = run("1\ntwo\n3").unwrap_err();
+        let frames = report.trace_log().frames();
+
+        assert_eq!(2, frames.len());
+        assert!(frames[0].location().file().ends_with("location.rs"));
+        assert_eq!("scripts/init.lua:2:0", frames[1].to_string());
+    }
+
+    #[test]
+    fn copied_paths_are_interned_once() {
+        let rust = Location::caller();
+        assert_eq!(CompactLocation::from(rust), CompactLocation::of(rust));
+
+        let first = CompactLocation::of(&SourcePosition::new(
+            &String::from("templates/page.html"),
+            1,
+            1,
+        ));
+        let again = CompactLocation::of(&SourcePosition::new(
+            &String::from("templates/page.html"),
+            9,
+            1,
+        ));
+
+        assert_eq!(first.file, again.file);
+        assert_eq!("templates/page.html", again.file());
+    }
+}
@@ -0,0 +1,231 @@
+//! Markdown rendering of traced errors.
+//!
+//! Bots that report failures to issue trackers or chat want to paste a traced error as is. A
+//! `MarkdownFormatter` renders the error message and its `source` chain in a fenced code block,
+//! its metadata as a table, and its trace as a numbered list, which is folded into a `<details>`
+//! section when it's long enough to drown out the rest of the message. Given a base URL for the
+//! repository, each frame links to the line of source it points at.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::markdown::MarkdownFormatter;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let markdown = MarkdownFormatter::new()
+//!     .link_base("https://github.com/seanchen1991/trial-and-error/blob/main/")
+//!     .render(&parse("four").unwrap_err());
+//!
+//! assert!(markdown.contains("](https://github.com/seanchen1991/trial-and-error/blob/main/src/"));
+//! ```
+
+use std::fmt::{self, Write};
+
+use super::visit::{Collector, Visit};
+use super::{FrameKind, TraceFrame};
+
+/// Renders traced errors as Markdown.
+#[derive(Debug, Clone)]
+pub struct MarkdownFormatter {
+    /// URL that frame paths are appended to when linking to them.
+    link_base: Option<String>,
+    /// Traces with more frames than this are folded into a `<details>` section.
+    collapse_after: usize,
+}
+
+impl Default for MarkdownFormatter {
+    fn default() -> Self {
+        MarkdownFormatter {
+            link_base: None,
+            collapse_after: 10,
+        }
+    }
+}
+
+impl MarkdownFormatter {
+    /// Create a new `MarkdownFormatter` that doesn't link frames and folds traces longer than 10
+    /// frames.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Link each frame to `base` followed by its path and a `#L<line>` anchor, which is the
+    /// layout GitHub and GitLab use for links to source.
+    pub fn link_base(mut self, base: impl Into<String>) -> Self {
+        self.link_base = Some(base.into());
+        self
+    }
+
+    /// Fold traces with more than `frames` frames into a `<details>` section.
+    pub fn collapse_after(mut self, frames: usize) -> Self {
+        self.collapse_after = frames;
+        self
+    }
+
+    /// Render a traced error as Markdown.
+    pub fn render<E>(&self, error: &E) -> String
+    where
+        E: Visit + ?Sized,
+    {
+        let mut markdown = String::new();
+        // Writing to a `String` can't fail
+        let _ = self.write(&mut markdown, &Collector::collect(error));
+        markdown
+    }
+
+    fn write(&self, out: &mut String, error: &Collector) -> fmt::Result {
+        let mut text = error.message.clone();
+        if !error.sources.is_empty() {
+            text.push_str("\n\nCaused by:");
+            for (index, source) in error.sources.iter().enumerate() {
+                write!(text, "\n{:>5}: {}", index, source)?;
+            }
+        }
+
+        let fence = fence_for(&text);
+        writeln!(out, "{}text\n{}\n{}", fence, text, fence)?;
+
+        if !error.metadata.is_empty() {
+            out.push_str("\n| | |\n|---|---|\n");
+            for (key, value) in &error.metadata {
+                writeln!(out, "| {} | `{}` |", key, value.replace('|', "\\|"))?;
+            }
+        }
+
+        if error.frames.is_empty() {
+            return fmt::Result::Ok(());
+        }
+
+        let collapsed = error.frames.len() > self.collapse_after;
+        if collapsed {
+            writeln!(
+                out,
+                "\n<details>\n<summary>Error return trace ({} frames)</summary>\n",
+                error.frames.len()
+            )?;
+        } else {
+            out.push_str("\n**Error return trace:**\n\n");
+        }
+
+        for (index, frame) in error.frames.iter().enumerate() {
+            write!(out, "{}. ", index)?;
+            self.write_frame(out, frame)?;
+            out.push('\n');
+        }
+
+        if collapsed {
+            out.push_str("\n</details>\n");
+        }
+
+        fmt::Result::Ok(())
+    }
+
+    fn write_frame(&self, out: &mut String, frame: &TraceFrame) -> fmt::Result {
+        let location = frame.location();
+
+        match &self.link_base {
+            Some(base) => write!(
+                out,
+                "[`{}`]({}{}#L{})",
+                location,
+                base,
+                location.file(),
+                location.line()
+            )?,
+            None => write!(out, "`{}`", location)?,
+        }
+
+        match frame.kind() {
+            FrameKind::Propagated => {}
+            FrameKind::Converted => out.push_str(" (converted here)"),
+            FrameKind::Sent => write!(
+                out,
+                " (sent across channel by thread {})",
+                frame.thread().unwrap_or_default()
+            )?,
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+/// A code fence long enough that no run of backticks in `text` closes it early.
+fn fence_for(text: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+
+    for c in text.chars() {
+        if c == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+
+    "`".repeat(longest.max(2) + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Ok, Result, TracedError, TracedReport};
+
+    fn parse(input: &str) -> Result<u32, TracedError<std::num::ParseIntError>> {
+        let value = input.parse::<u32>()?;
+        Ok(value)
+    }
+
+    fn report(input: &str) -> Result<u32, TracedReport> {
+        let value = parse(input)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn renders_message_metadata_and_frames() {
+        let markdown = MarkdownFormatter::new().render(&parse("x").unwrap_err());
+
+        assert!(markdown.starts_with("```text\ninvalid digit found in string\n```\n"));
+        assert!(markdown.contains("| type | `core::num::error::ParseIntError` |"));
+        assert!(markdown.contains("**Error return trace:**\n\n0. `src/return_trace/markdown.rs:"));
+        assert!(!markdown.contains("<details>"));
+    }
+
+    #[test]
+    fn long_traces_are_collapsed() {
+        let markdown = MarkdownFormatter::new()
+            .collapse_after(1)
+            .render(&report("x").unwrap_err());
+
+        assert!(markdown.contains("<summary>Error return trace (3 frames)</summary>"));
+        assert!(markdown.contains("(converted here)"));
+        assert!(markdown.trim_end().ends_with("</details>"));
+    }
+
+    #[test]
+    fn frames_link_to_source() {
+        let error = parse("x").unwrap_err();
+        let line = error.trace_log().frames()[0].location().line();
+        let markdown = MarkdownFormatter::new()
+            .link_base("https://example.com/repo/")
+            .render(&error);
+
+        let link = format!(
+            "(https://example.com/repo/src/return_trace/markdown.rs#L{})",
+            line
+        );
+        assert!(markdown.contains(&link));
+    }
+
+    #[test]
+    fn fences_outgrow_backticks_in_message() {
+        assert_eq!("```", fence_for("no ticks"));
+        assert_eq!("````", fence_for("has ``` inside"));
+    }
+}
@@ -0,0 +1,162 @@
+//! Accounting for the memory held by traces.
+//!
+//! Deep traces, captured values, and backtraces all live on the heap for as long as the error
+//! holding them does. `TraceLog::memory_usage` estimates how much a single trace holds, and with
+//! accounting turned on with `set_memory_accounting`, every `TraceLog` adds what it holds to a
+//! global tally as it grows and takes it back when it's dropped, so `trace_memory` can report how
+//! much memory live traces are holding at any point. Accounting is off by default, since it adds
+//! work to every frame recorded.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::memory::{self, set_memory_accounting};
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! set_memory_accounting(true);
+//!
+//! let error = parse("four").unwrap_err();
+//! let usage = error.trace_log().memory_usage();
+//!
+//! assert!(memory::trace_memory().bytes >= usage as u64);
+//! ```
+
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::age::HopTime;
+use super::capture::Capture;
+use super::deep_trace::DeepFrame;
+use super::{push_flags, set_push_flag, TraceFrame, TraceLog, MEMORY_ACCOUNTING};
+
+static LIVE_TRACES: AtomicU64 = AtomicU64::new(0);
+
+static LIVE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The memory held by every live trace, as counted while accounting was on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TraceMemory {
+    /// The number of traces holding memory.
+    pub traces: u64,
+    /// The number of bytes they hold.
+    pub bytes: u64,
+}
+
+/// Turn accounting for the memory held by traces on or off.
+///
+/// Traces only add to the tally while accounting is on, but always take back what they added
+/// when they are dropped, so the tally never counts memory that was freed.
+pub fn set_memory_accounting(enabled: bool) {
+    set_push_flag(MEMORY_ACCOUNTING, enabled);
+}
+
+/// Whether accounting for the memory held by traces is on.
+pub fn memory_accounting() -> bool {
+    push_flags() & MEMORY_ACCOUNTING != 0
+}
+
+/// The memory held by every live trace that grew while accounting was on.
+///
+/// Frames shared between clones of a trace are counted once for every clone.
+pub fn trace_memory() -> TraceMemory {
+    TraceMemory {
+        traces: LIVE_TRACES.load(Ordering::Relaxed),
+        bytes: LIVE_BYTES.load(Ordering::Relaxed),
+    }
+}
+
+/// The size of the shared allocation behind an `Arc<T>`, without what it points to.
+fn arc_size<T>() -> usize {
+    2 * mem::size_of::<usize>() + mem::size_of::<T>()
+}
+
+impl TraceLog {
+    /// An estimate of the heap memory held by the trace, in bytes.
+    ///
+    /// This counts the frames, the values captured with `capture!`, the backtraces captured with
+    /// `deep_trace!`, and the times of the frames, but not the symbols a backtrace resolves when
+    /// it's printed. Memory shared with clones of the trace is counted in full.
+    pub fn memory_usage(&self) -> usize {
+        let mut bytes =
+            arc_size::<Vec<TraceFrame>>() + self.frames.capacity() * mem::size_of::<TraceFrame>();
+
+        if let Some(captures) = &self.captures {
+            bytes += arc_size::<Vec<Capture>>() + captures.capacity() * mem::size_of::<Capture>();
+            bytes += captures
+                .iter()
+                .map(|capture| capture.value().len())
+                .sum::<usize>();
+        }
+
+        if let Some(deep_frames) = &self.deep_frames {
+            bytes += arc_size::<Vec<DeepFrame>>()
+                + deep_frames.capacity() * mem::size_of::<DeepFrame>()
+                + deep_frames.len() * arc_size::<std::backtrace::Backtrace>();
+        }
+
+        if let Some(hop_times) = &self.hop_times {
+            bytes += arc_size::<Vec<HopTime>>() + hop_times.capacity() * mem::size_of::<HopTime>();
+        }
+
+        bytes
+    }
+
+    /// Bring what the trace adds to the global tally up to date, if accounting is on.
+    #[inline]
+    pub(crate) fn account(&mut self) {
+        if memory_accounting() {
+            self.reaccount();
+        }
+    }
+
+    /// Bring what the trace adds to the global tally up to date.
+    #[cold]
+    pub(crate) fn reaccount(&mut self) {
+        let bytes = self.memory_usage();
+
+        if self.accounted == 0 {
+            LIVE_TRACES.fetch_add(1, Ordering::Relaxed);
+        }
+        LIVE_BYTES.fetch_add(bytes as u64, Ordering::Relaxed);
+        LIVE_BYTES.fetch_sub(self.accounted as u64, Ordering::Relaxed);
+        self.accounted = bytes;
+    }
+
+    /// Take back what the trace added to the global tally.
+    pub(crate) fn unaccount(&mut self) {
+        if self.accounted > 0 {
+            LIVE_TRACES.fetch_sub(1, Ordering::Relaxed);
+            LIVE_BYTES.fetch_sub(self.accounted as u64, Ordering::Relaxed);
+            self.accounted = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+
+    // The tally is global, so the tests that check it live in `tests/memory_accounting.rs`
+
+    #[test]
+    fn usage_grows_with_frames_and_captures() {
+        let mut trace = TraceLog::new();
+        let empty = trace.memory_usage();
+
+        for _ in 0..8 {
+            trace.push(Location::caller());
+        }
+        let with_frames = trace.memory_usage();
+        assert!(with_frames >= empty + 8 * mem::size_of::<TraceFrame>());
+
+        trace.capture("user", "x".repeat(100));
+        assert!(trace.memory_usage() >= with_frames + 100);
+    }
+}
@@ -0,0 +1,191 @@
+//! Converting panics into traced errors.
+//!
+//! Supervisory loops often need to treat a panicking unit of work the same as one that returned
+//! an error. `catch_traced` runs a closure with `std::panic::catch_unwind`, converting a panic
+//! into a `TracedError<PanicError>` that carries the panic message and location, with the call to
+//! `catch_traced` as the first frame of its trace.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::panic::catch_traced;
+//!
+//! let error = catch_traced(|| -> u32 { panic!("worker exploded") }).unwrap_err();
+//!
+//! assert_eq!(Some("worker exploded"), error.inner().message());
+//! assert_eq!(1, error.trace_log().len());
+//! ```
+//!
+//! The panic location is captured with a panic hook that is installed the first time
+//! `catch_traced` is called. It calls the previously registered hook, so panics are still
+//! printed as usual.
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self as std_panic, Location, PanicInfo, UnwindSafe};
+use std::sync::Once;
+
+use super::{record, Err, Ok, Result, TracedError};
+
+/// Owned copy of the location a panic occurred at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicLocation {
+    file: String,
+    line: u32,
+    column: u32,
+}
+
+impl PanicLocation {
+    /// The file the panic occurred in.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// The line the panic occurred at.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// The column the panic occurred at.
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+}
+
+impl<'a> From<&Location<'a>> for PanicLocation {
+    fn from(location: &Location<'a>) -> Self {
+        PanicLocation {
+            file: location.file().to_owned(),
+            line: location.line(),
+            column: location.column(),
+        }
+    }
+}
+
+impl fmt::Display for PanicLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// Error produced by `catch_traced` when the closure panics.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PanicError {
+    /// The panic message, if the payload was a string.
+    message: Option<String>,
+    /// Where the panic occurred, if it was captured.
+    location: Option<PanicLocation>,
+}
+
+impl PanicError {
+    /// The panic message, if the panic payload was a `&str` or `String`.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Where the panic occurred, if it was captured.
+    pub fn location(&self) -> Option<&PanicLocation> {
+        self.location.as_ref()
+    }
+}
+
+impl fmt::Display for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "panicked at '{}'", message)?,
+            None => f.write_str("panicked at 'Box<dyn Any>'")?,
+        }
+
+        if let Some(location) = &self.location {
+            write!(f, ", {}", location)?;
+        }
+
+        fmt::Result::Ok(())
+    }
+}
+
+impl Error for PanicError {}
+
+thread_local! {
+    // The location of the most recent panic on this thread, set by the hook
+    static PANIC_LOCATION: RefCell<Option<PanicLocation>> = RefCell::new(None);
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Install a panic hook that records panic locations before calling the previous hook.
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = std_panic::take_hook();
+
+        std_panic::set_hook(Box::new(move |info| {
+            record_location(info);
+            previous(info);
+        }));
+    });
+}
+
+/// Remember where the panic described by `info` happened, for `catch_traced` to pick up.
+pub(crate) fn record_location(info: &PanicInfo<'_>) {
+    let location = info.location().map(PanicLocation::from);
+    PANIC_LOCATION.with(|cell| *cell.borrow_mut() = location);
+}
+
+/// Run `f`, converting a panic into a `TracedError<PanicError>` with the call site as the first
+/// frame.
+#[track_caller]
+pub fn catch_traced<F, T>(f: F) -> Result<T, TracedError<PanicError>>
+where
+    F: FnOnce() -> T + UnwindSafe,
+{
+    let caller = Location::caller();
+
+    install_hook();
+    PANIC_LOCATION.with(|cell| cell.borrow_mut().take());
+
+    match std_panic::catch_unwind(f) {
+        std::result::Result::Ok(value) => Ok(value),
+        std::result::Result::Err(payload) => {
+            let message = match payload.downcast::<&'static str>() {
+                std::result::Result::Ok(message) => Some(String::from(*message)),
+                std::result::Result::Err(payload) => payload.downcast::<String>().ok().map(|m| *m),
+            };
+            let location = PANIC_LOCATION.with(|cell| cell.borrow_mut().take());
+
+            let mut error = TracedError::new(PanicError { message, location });
+            record(&mut error, caller);
+            Err(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_become_traced_errors_with_location() {
+        let error = catch_traced(|| -> u32 { panic!("oh no: {}", 4) }).unwrap_err();
+        let panic_location = error.inner().location().unwrap();
+
+        assert_eq!(Some("oh no: 4"), error.inner().message());
+        assert_eq!(file!(), panic_location.file());
+        assert_eq!(line!() - 5, panic_location.line());
+        assert_eq!(line!() - 6, error.trace_log().frames()[0].location().line());
+        assert!(error
+            .to_string()
+            .starts_with("panicked at 'oh no: 4', src/return_trace/panic.rs:"));
+    }
+
+    #[test]
+    fn non_string_payloads_have_no_message() {
+        let error = catch_traced(|| std_panic::panic_any(4_u32)).unwrap_err();
+
+        assert_eq!(None, error.inner().message());
+        assert!(error.to_string().starts_with("panicked at 'Box<dyn Any>'"));
+    }
+
+    #[test]
+    fn return_values_pass_through() {
+        assert_eq!(Some(4), catch_traced(|| 4).ok());
+    }
+}
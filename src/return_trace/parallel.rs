@@ -0,0 +1,143 @@
+//! Rayon support for traced results.
+//!
+//! Traced `Result`s can be collected from parallel iterators just like `std::result::Result`s,
+//! short-circuiting on the first error that is found. The error that wins keeps its trace.
+//!
+//! Rayon's `collect` can't report where it was called from, so `collect_traced` should be used
+//! instead when the parallel collect site should also show up in the trace.
+//!
+//! ```rust
+//! use rayon::prelude::*;
+//! use trial_and_error::return_trace::parallel::ParallelIteratorExt;
+//! use trial_and_error::return_trace::{Result, ResultExt, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<std::num::ParseIntError>> {
+//!     input.parse::<u32>().traced()
+//! }
+//!
+//! let inputs = vec!["1", "2", "nope", "4"];
+//! let error = inputs
+//!     .par_iter()
+//!     .map(|input| parse(input))
+//!     .collect_traced::<Vec<_>>()
+//!     .unwrap_err();
+//!
+//! assert_eq!(2, error.trace_log().len());
+//! ```
+
+use std::panic::Location;
+
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use super::{record, Err, MaybeTraced, Ok, Result};
+
+impl<C, T, E> FromParallelIterator<Result<T, E>> for Result<C, E>
+where
+    C: FromParallelIterator<T>,
+    T: Send,
+    E: MaybeTraced + Send,
+{
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = Result<T, E>>,
+    {
+        // Reuse rayon's short-circuiting collect for `std::result::Result`
+        let collected: std::result::Result<C, E> = par_iter
+            .into_par_iter()
+            .map(|result| match result {
+                Ok(value) => std::result::Result::Ok(value),
+                Err(error) => std::result::Result::Err(error),
+            })
+            .collect();
+
+        match collected {
+            std::result::Result::Ok(collection) => Ok(collection),
+            std::result::Result::Err(error) => Err(error),
+        }
+    }
+}
+
+/// Extension trait for collecting parallel iterators of traced `Result`s.
+pub trait ParallelIteratorExt<T, E>: ParallelIterator<Item = Result<T, E>>
+where
+    T: Send,
+    E: MaybeTraced + Send,
+{
+    /// Collect the results, short-circuiting on the first error found and recording the call
+    /// site in its trace.
+    fn collect_traced<C>(self) -> Result<C, E>
+    where
+        C: FromParallelIterator<T>;
+}
+
+impl<I, T, E> ParallelIteratorExt<T, E> for I
+where
+    I: ParallelIterator<Item = Result<T, E>>,
+    T: Send,
+    E: MaybeTraced + Send,
+{
+    #[track_caller]
+    fn collect_traced<C>(self) -> Result<C, E>
+    where
+        C: FromParallelIterator<T>,
+    {
+        let location = Location::caller();
+
+        match self.collect() {
+            Ok(collection) => Ok(collection),
+            Err(mut error) => {
+                record(&mut error, location);
+                Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::num::ParseIntError;
+
+    use rayon::prelude::*;
+
+    use crate::return_trace::{ResultExt, TracedError};
+
+    fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+        input.parse::<u32>().traced()
+    }
+
+    #[test]
+    fn collect_gathers_ok_values_in_order() {
+        let values: Result<Vec<_>, _> = (1..=100)
+            .into_par_iter()
+            .map(|value| parse(&value.to_string()))
+            .collect();
+
+        assert_eq!(Some((1..=100).collect()), values.ok());
+    }
+
+    #[test]
+    fn collect_short_circuits_on_error() {
+        let inputs = vec!["1", "nope", "3"];
+        let error = inputs
+            .par_iter()
+            .map(|input| parse(input))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_err();
+
+        assert_eq!(1, error.trace_log().len());
+    }
+
+    #[test]
+    fn collect_traced_records_collect_site() {
+        let inputs = vec!["1", "nope", "3"];
+        let error = inputs
+            .par_iter()
+            .map(|input| parse(input))
+            .collect_traced::<Vec<_>>()
+            .unwrap_err();
+
+        assert_eq!(2, error.trace_log().len());
+    }
+}
@@ -0,0 +1,180 @@
+//! Traced errors for poisoned locks.
+//!
+//! A `PoisonError` only says that some thread panicked while holding a lock, not which lock, or
+//! where the program tried to take it. `MutexExt::lock_traced` and `RwLockExt::read_traced` and
+//! `write_traced` take a lock like their std counterparts, but wrap a `PoisonError` in a
+//! `TracedError` with the call site as its first frame, so the trace starts where the poisoned
+//! lock was acquired.
+//!
+//! The guard in a `PoisonError` borrows the lock, so `TracedPoison` can't be converted into a
+//! `TracedReport` as is. Converting it into a `Poisoned` first drops the guard and keeps the rest.
+//!
+//! ```rust
+//! use std::sync::{Arc, Mutex};
+//! use std::thread;
+//! use trial_and_error::return_trace::poison::{MutexExt, Poisoned};
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn increment(counter: &Mutex<u32>) -> Result<u32, TracedError<Poisoned>> {
+//!     let mut count = counter.lock_traced().map_err(|error| error.convert())?;
+//!     *count += 1;
+//!
+//!     Ok(*count)
+//! }
+//!
+//! let counter = Arc::new(Mutex::new(0));
+//! let poisoner = Arc::clone(&counter);
+//! let _ = thread::spawn(move || {
+//!     let _count = poisoner.lock().unwrap();
+//!     panic!("poisoning the lock");
+//! })
+//! .join();
+//!
+//! let error = increment(&counter).unwrap_err();
+//!
+//! assert_eq!(3, error.trace_log().len());
+//! ```
+
+use std::any;
+use std::error::Error;
+use std::fmt;
+use std::panic::Location;
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{record, Err, Ok, Result, TracedError};
+
+/// A `PoisonError` holding the guard `G`, with a trace starting where the lock was acquired.
+pub type TracedPoison<G> = TracedError<PoisonError<G>>;
+
+/// Error for a poisoned lock, without the guard of the `PoisonError` it was converted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poisoned {
+    /// The name of the type of the guard.
+    guard: &'static str,
+}
+
+impl Poisoned {
+    /// The name of the type of the guard the lock would have returned.
+    pub fn guard_type(&self) -> &'static str {
+        self.guard
+    }
+}
+
+impl<G> From<PoisonError<G>> for Poisoned {
+    fn from(_: PoisonError<G>) -> Self {
+        Poisoned {
+            guard: any::type_name::<G>(),
+        }
+    }
+}
+
+impl fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lock returning `{}` was poisoned by a thread that panicked while holding it",
+            self.guard
+        )
+    }
+}
+
+impl Error for Poisoned {}
+
+/// Convert the result of taking a lock, recording `location` if the lock was poisoned.
+fn traced<G>(
+    result: std::sync::LockResult<G>,
+    location: &'static Location<'static>,
+) -> Result<G, TracedPoison<G>> {
+    match result {
+        std::result::Result::Ok(guard) => Ok(guard),
+        std::result::Result::Err(error) => {
+            let mut error = TracedError::new(error);
+            record(&mut error, location);
+            Err(error)
+        }
+    }
+}
+
+/// Extension trait for taking a `Mutex` with a traced error if it was poisoned.
+pub trait MutexExt<T: ?Sized> {
+    /// Lock the mutex like `Mutex::lock`, recording the call site as the first frame if it was
+    /// poisoned.
+    fn lock_traced(&self) -> Result<MutexGuard<'_, T>, TracedPoison<MutexGuard<'_, T>>>;
+}
+
+impl<T: ?Sized> MutexExt<T> for Mutex<T> {
+    #[track_caller]
+    fn lock_traced(&self) -> Result<MutexGuard<'_, T>, TracedPoison<MutexGuard<'_, T>>> {
+        traced(self.lock(), Location::caller())
+    }
+}
+
+/// Extension trait for taking a `RwLock` with a traced error if it was poisoned.
+pub trait RwLockExt<T: ?Sized> {
+    /// Lock the lock for reading like `RwLock::read`, recording the call site as the first frame
+    /// if it was poisoned.
+    fn read_traced(&self) -> Result<RwLockReadGuard<'_, T>, TracedPoison<RwLockReadGuard<'_, T>>>;
+
+    /// Lock the lock for writing like `RwLock::write`, recording the call site as the first frame
+    /// if it was poisoned.
+    fn write_traced(
+        &self,
+    ) -> Result<RwLockWriteGuard<'_, T>, TracedPoison<RwLockWriteGuard<'_, T>>>;
+}
+
+impl<T: ?Sized> RwLockExt<T> for RwLock<T> {
+    #[track_caller]
+    fn read_traced(&self) -> Result<RwLockReadGuard<'_, T>, TracedPoison<RwLockReadGuard<'_, T>>> {
+        traced(self.read(), Location::caller())
+    }
+
+    #[track_caller]
+    fn write_traced(
+        &self,
+    ) -> Result<RwLockWriteGuard<'_, T>, TracedPoison<RwLockWriteGuard<'_, T>>> {
+        traced(self.write(), Location::caller())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// Run `hold` and panic while the guard it returns is still alive, poisoning its lock.
+    fn poison<G>(hold: impl FnOnce() -> G) {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = hold();
+            panic!("poisoning the lock");
+        }));
+    }
+
+    #[test]
+    fn poisoned_mutex_records_where_it_was_locked() {
+        let mutex = Mutex::new(1);
+        assert_eq!(Some(1), mutex.lock_traced().ok().map(|guard| *guard));
+
+        poison(|| mutex.lock());
+
+        let line = line!() + 1;
+        let error = mutex.lock_traced().unwrap_err();
+        let frames = error.trace_log().frames();
+
+        assert_eq!(1, frames.len());
+        assert_eq!(line, frames[0].location().line());
+        assert_eq!(1, *error.into_inner().into_inner());
+    }
+
+    #[test]
+    fn poisoned_rwlock_converts_into_poisoned() {
+        let lock = RwLock::new(());
+        poison(|| lock.write());
+
+        assert!(lock.read_traced().is_err());
+
+        let error: TracedError<Poisoned> = lock.write_traced().unwrap_err().convert();
+        assert_eq!(2, error.trace_log().len());
+        assert!(error.inner().guard_type().contains("RwLockWriteGuard"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Rate limiting the traces handed to hooks.
+//!
+//! When something upstream breaks, the same error can be propagated millions of times a minute,
+//! and a hook that logs or reports every trace floods its destination with copies of the same one.
+//! A `RateLimiter` keeps a token bucket per `Fingerprint`, so the first occurrences along each
+//! path get through in full while repeats beyond the limit are only counted. `rate_limited` wraps
+//! a hook so it is only called for the traces the limiter lets through.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use trial_and_error::return_trace::rate_limit::{rate_limited, RateLimiter};
+//! use trial_and_error::return_trace::set_boundary_hook;
+//!
+//! // Report at most 10 traces along each path, and one more every second after that
+//! let limiter = RateLimiter::new(10, Duration::from_secs(1));
+//!
+//! set_boundary_hook(rate_limited(limiter, |trace| eprintln!("{}", trace)));
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::fingerprint::Fingerprint;
+use super::TraceLog;
+
+/// Buckets are dropped once they refill if there are more than this many.
+const MAX_BUCKETS: usize = 4096;
+
+#[derive(Debug)]
+struct Bucket {
+    /// The number of traces that can still get through.
+    tokens: u32,
+    /// When a token was last added, or the bucket was created.
+    refilled: Instant,
+}
+
+/// Token buckets of traces, one per fingerprint.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// The number of tokens in a full bucket.
+    capacity: u32,
+    /// How long it takes a bucket to gain a token.
+    refill_every: Duration,
+    buckets: Mutex<HashMap<Fingerprint, Bucket>>,
+    suppressed: AtomicU64,
+}
+
+impl RateLimiter {
+    /// Create a new `RateLimiter` that lets through `capacity` traces along each path at once,
+    /// and one more every `refill_every` after that.
+    pub fn new(capacity: u32, refill_every: Duration) -> Self {
+        RateLimiter {
+            capacity,
+            refill_every,
+            buckets: Mutex::new(HashMap::new()),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether `trace` should get through, taking a token from the bucket of its fingerprint if
+    /// so.
+    pub fn allow(&self, trace: &TraceLog) -> bool {
+        self.allow_fingerprint(trace.fingerprint())
+    }
+
+    /// Whether a trace with `fingerprint` should get through, taking a token from its bucket if
+    /// so.
+    pub fn allow_fingerprint(&self, fingerprint: Fingerprint) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() >= MAX_BUCKETS && !buckets.contains_key(&fingerprint) {
+            buckets.retain(|_, bucket| {
+                self.refill(bucket, now);
+                bucket.tokens < self.capacity
+            });
+        }
+
+        let capacity = self.capacity;
+        let bucket = buckets.entry(fingerprint).or_insert(Bucket {
+            tokens: capacity,
+            refilled: now,
+        });
+        self.refill(bucket, now);
+
+        if bucket.tokens > 0 {
+            bucket.tokens -= 1;
+            true
+        } else {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// The number of traces that didn't get through so far.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    /// Add the tokens `bucket` gained since it was last refilled.
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        if bucket.tokens >= self.capacity {
+            bucket.refilled = now;
+            return;
+        }
+
+        let interval = self.refill_every.as_nanos();
+        if interval == 0 {
+            bucket.tokens = self.capacity;
+            return;
+        }
+
+        let gained = now.duration_since(bucket.refilled).as_nanos() / interval;
+        if gained == 0 {
+            return;
+        }
+
+        let gained = u32::try_from(gained).unwrap_or(u32::MAX);
+        bucket.tokens = bucket.tokens.saturating_add(gained).min(self.capacity);
+        bucket.refilled = if bucket.tokens == self.capacity {
+            now
+        } else {
+            bucket.refilled + self.refill_every * gained
+        };
+    }
+}
+
+/// Wrap `hook` so it's only called with the traces `limiter` lets through.
+pub fn rate_limited<F>(
+    limiter: RateLimiter,
+    hook: F,
+) -> Box<dyn Fn(&TraceLog) + Send + Sync + 'static>
+where
+    F: Fn(&TraceLog) + Send + Sync + 'static,
+{
+    Box::new(move |trace| {
+        if limiter.allow(trace) {
+            hook(trace);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn trace(location: &'static Location<'static>) -> TraceLog {
+        let mut trace = TraceLog::new();
+        trace.push(location);
+        trace
+    }
+
+    #[test]
+    fn limits_each_fingerprint_separately() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(3600));
+        let first = trace(Location::caller());
+        let second = trace(Location::caller());
+
+        assert!(limiter.allow(&first));
+        assert!(limiter.allow(&first));
+        assert!(!limiter.allow(&first));
+        assert!(limiter.allow(&second));
+        assert_eq!(1, limiter.suppressed());
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(1));
+        let trace = trace(Location::caller());
+
+        assert!(limiter.allow(&trace));
+        thread::sleep(Duration::from_millis(5));
+        assert!(limiter.allow(&trace));
+    }
+
+    #[test]
+    fn wrapped_hook_only_sees_allowed_traces() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = Arc::clone(&calls);
+        let hook = rate_limited(RateLimiter::new(3, Duration::from_secs(3600)), move |_| {
+            counted.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let trace = trace(Location::caller());
+        for _ in 0..10 {
+            hook(&trace);
+        }
+
+        assert_eq!(3, calls.load(Ordering::Relaxed));
+    }
+}
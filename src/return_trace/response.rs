@@ -0,0 +1,371 @@
+//! Turning traced errors into HTTP error responses.
+//!
+//! Every web service ends up writing the same error handler: respond with a bare 500 and an id to
+//! quote to support in production, and with the whole error while debugging. `ErrorResponse`
+//! builds that response from a traced error, independently of any web framework, so adapting it
+//! only takes copying its status, content type, and body into the framework's response type.
+//!
+//! By default responses only contain a correlation ID, which is the trace id of the error's
+//! `traceparent` if it has one, or a randomly generated one otherwise. `set_debug_responses`
+//! switches every response to include the rendered error instead.
+//!
+//! Responses are 500s unless the mapper registered with `set_status_mapper` picks a status for the
+//! error or one of its sources.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::response::ErrorResponse;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let response = ErrorResponse::new(&parse("four").unwrap_err());
+//!
+//! assert_eq!(500, response.status());
+//! assert_eq!("application/json", response.content_type());
+//! assert!(response.body().contains(response.correlation_id()));
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::hash::{BuildHasher, Hasher};
+use std::lazy::SyncLazy;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::RwLock;
+
+use super::visit::{Collector, TraceVisitor, Visit};
+use super::TraceFrame;
+
+/// How the error is rendered in responses when debug responses are enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// A JSON object with the error's message, metadata, frames, and sources.
+    Json,
+    /// A standalone HTML page, as rendered by `html::page`.
+    #[cfg(feature = "html")]
+    Html,
+}
+
+const PRODUCTION: u8 = 0;
+const DEBUG_JSON: u8 = 1;
+#[cfg(feature = "html")]
+const DEBUG_HTML: u8 = 2;
+
+static RESPONSE_MODE: AtomicU8 = AtomicU8::new(PRODUCTION);
+
+/// Make every `ErrorResponse` include the rendered error in `format`, or only a correlation ID if
+/// `format` is `None`.
+///
+/// Debug responses expose the internals of the service, so they should never be enabled in
+/// production.
+pub fn set_debug_responses(format: Option<ResponseFormat>) {
+    let mode = match format {
+        None => PRODUCTION,
+        Some(ResponseFormat::Json) => DEBUG_JSON,
+        #[cfg(feature = "html")]
+        Some(ResponseFormat::Html) => DEBUG_HTML,
+    };
+
+    RESPONSE_MODE.store(mode, Ordering::Relaxed);
+}
+
+/// The format errors are rendered in, if debug responses are enabled.
+pub fn debug_responses() -> Option<ResponseFormat> {
+    match RESPONSE_MODE.load(Ordering::Relaxed) {
+        DEBUG_JSON => Some(ResponseFormat::Json),
+        #[cfg(feature = "html")]
+        DEBUG_HTML => Some(ResponseFormat::Html),
+        _ => None,
+    }
+}
+
+/// Picks the status code of responses for the errors it recognizes.
+type StatusMapper = Box<dyn Fn(&(dyn Error + 'static)) -> Option<u16> + Send + Sync + 'static>;
+
+static STATUS_MAPPER: SyncLazy<RwLock<Option<StatusMapper>>> = SyncLazy::new(|| RwLock::new(None));
+
+/// Register a mapper that picks the status code of every `ErrorResponse`, replacing any
+/// previously registered mapper.
+///
+/// The mapper is called with the traced error, then with each error in its `source` chain, until
+/// it returns a status. Errors it doesn't recognize get a 500. The error of a `TracedReport` that
+/// was converted from a `TracedError` is the `TracedError` itself, so mappers should look for both.
+pub fn set_status_mapper(mapper: StatusMapper) {
+    *STATUS_MAPPER.write().unwrap_or_else(|e| e.into_inner()) = Some(mapper);
+}
+
+/// Unregister the current status mapper, returning it.
+pub fn take_status_mapper() -> Option<StatusMapper> {
+    STATUS_MAPPER
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+}
+
+/// Visitor that asks the status mapper about the error and its sources.
+struct StatusOf<'a> {
+    mapper: &'a StatusMapper,
+    status: Option<u16>,
+}
+
+impl TraceVisitor for StatusOf<'_> {
+    fn visit_frame(&mut self, _index: usize, _frame: &TraceFrame) {}
+
+    fn visit_error(&mut self, error: &(dyn Error + 'static)) {
+        self.status = self.status.or_else(|| (self.mapper)(error));
+    }
+
+    fn visit_source(&mut self, _depth: usize, source: &(dyn Error + 'static)) {
+        self.status = self.status.or_else(|| (self.mapper)(source));
+    }
+}
+
+/// The status the status mapper picks for `error`, or 500.
+fn status_of<E>(error: &E) -> u16
+where
+    E: Visit + ?Sized,
+{
+    let mapper = STATUS_MAPPER.read().unwrap_or_else(|e| e.into_inner());
+
+    let status = mapper.as_ref().and_then(|mapper| {
+        let mut visitor = StatusOf {
+            mapper,
+            status: None,
+        };
+        error.visit(&mut visitor);
+        visitor.status
+    });
+
+    status.unwrap_or(500)
+}
+
+/// An HTTP response for a traced error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorResponse {
+    status: u16,
+    correlation_id: String,
+    content_type: &'static str,
+    body: String,
+}
+
+impl ErrorResponse {
+    /// Create a new response for a traced error, rendered according to `set_debug_responses`.
+    pub fn new<E>(error: &E) -> Self
+    where
+        E: Visit + ?Sized,
+    {
+        ErrorResponse::with_format(error, debug_responses())
+    }
+
+    /// Create a new response for a traced error, including the rendered error in `format`, or only
+    /// a correlation ID if `format` is `None`.
+    pub fn with_format<E>(error: &E, format: Option<ResponseFormat>) -> Self
+    where
+        E: Visit + ?Sized,
+    {
+        let collector = Collector::collect(error);
+        let correlation_id = correlation_id(&collector);
+
+        let (content_type, body) = match format {
+            None => {
+                let mut body = String::new();
+                let _ = write!(
+                    body,
+                    "{{\"error\":\"internal server error\",\"correlation_id\":{}}}",
+                    Json(&correlation_id)
+                );
+                ("application/json", body)
+            }
+            Some(ResponseFormat::Json) => {
+                let mut body = String::new();
+                let _ = write_json(&mut body, &collector, &correlation_id);
+                ("application/json", body)
+            }
+            #[cfg(feature = "html")]
+            Some(ResponseFormat::Html) => ("text/html; charset=utf-8", super::html::page(error)),
+        };
+
+        ErrorResponse {
+            status: status_of(error),
+            correlation_id,
+            content_type,
+            body,
+        }
+    }
+
+    /// Replace the status code of the response.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// The HTTP status code of the response.
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// The ID included in the response, to be logged alongside the error so the two can be found
+    /// from one another.
+    pub fn correlation_id(&self) -> &str {
+        &self.correlation_id
+    }
+
+    /// The value of the `Content-Type` header of the response.
+    pub fn content_type(&self) -> &'static str {
+        self.content_type
+    }
+
+    /// The body of the response.
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Unwrap the body of the response.
+    pub fn into_body(self) -> String {
+        self.body
+    }
+}
+
+/// The trace id of the error's `traceparent`, or a new random ID if it has none.
+fn correlation_id(error: &Collector) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let trace_id = error
+        .metadata
+        .iter()
+        .find(|(key, _)| key == "traceparent")
+        .and_then(|(_, value)| value.split('-').nth(1));
+
+    if let Some(trace_id) = trace_id {
+        return trace_id.to_string();
+    }
+
+    // `RandomState` is seeded randomly, which is all that's needed for IDs that only have to be
+    // unlikely to collide
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    let high = hasher.finish();
+    hasher.write_u64(high);
+
+    format!("{:016x}{:016x}", high, hasher.finish())
+}
+
+fn write_json(out: &mut String, error: &Collector, correlation_id: &str) -> fmt::Result {
+    write!(
+        out,
+        "{{\"error\":{},\"correlation_id\":{},\"metadata\":{{",
+        Json(&error.message),
+        Json(correlation_id)
+    )?;
+    for (index, (key, value)) in error.metadata.iter().enumerate() {
+        let comma = if index > 0 { "," } else { "" };
+        write!(out, "{}{}:{}", comma, Json(key), Json(value))?;
+    }
+
+    out.push_str("},\"frames\":[");
+    for (index, frame) in error.frames.iter().enumerate() {
+        let comma = if index > 0 { "," } else { "" };
+        write!(out, "{}{}", comma, Json(&frame.to_string()))?;
+    }
+
+    out.push_str("],\"sources\":[");
+    for (index, source) in error.sources.iter().enumerate() {
+        let comma = if index > 0 { "," } else { "" };
+        write!(out, "{}{}", comma, Json(source))?;
+    }
+    out.push_str("]}");
+
+    fmt::Result::Ok(())
+}
+
+/// Displays a string as a quoted JSON string.
+pub(crate) struct Json<'a>(pub(crate) &'a str);
+
+impl fmt::Display for Json<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::traceparent::TraceParent;
+    use crate::return_trace::{Ok, Result, TracedError};
+
+    fn parse(input: &str) -> Result<u32, TracedError<std::num::ParseIntError>> {
+        let value = input.parse::<u32>()?;
+        Ok(value)
+    }
+
+    // The status mapper is global, so the tests that set it live in `tests/status_mapper.rs`
+
+    #[test]
+    fn production_responses_only_have_correlation_id() {
+        let first = ErrorResponse::with_format(&parse("x").unwrap_err(), None);
+        let second = ErrorResponse::with_format(&parse("x").unwrap_err(), None);
+
+        assert_eq!(32, first.correlation_id().len());
+        assert_ne!(first.correlation_id(), second.correlation_id());
+        assert_eq!(
+            format!(
+                "{{\"error\":\"internal server error\",\"correlation_id\":\"{}\"}}",
+                first.correlation_id()
+            ),
+            first.body()
+        );
+    }
+
+    #[test]
+    fn debug_responses_render_the_error() {
+        let error = parse("x")
+            .unwrap_err()
+            .with_trace_parent(TraceParent::new(0xabc, 1));
+        let response =
+            ErrorResponse::with_format(&error, Some(ResponseFormat::Json)).with_status(400);
+
+        assert_eq!(400, response.status());
+        assert_eq!(
+            "00000000000000000000000000000abc",
+            response.correlation_id()
+        );
+
+        let body = response.body();
+        assert!(body.starts_with(
+            "{\"error\":\"invalid digit found in string\",\
+             \"correlation_id\":\"00000000000000000000000000000abc\",\
+             \"metadata\":{\"type\":\"core::num::error::ParseIntError\","
+        ));
+        assert!(body.contains(&format!(
+            "\"frames\":[\"{}\"]",
+            error.trace_log().frames()[0]
+        )));
+        assert!(body.ends_with("\"sources\":[]}"));
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(
+            "\"a \\\"quote\\\"\\n\\\\ \\u0001\"",
+            Json("a \"quote\"\n\\ \u{1}").to_string()
+        );
+    }
+}
@@ -0,0 +1,399 @@
+//! Setting up traced errors for an application in one call.
+//!
+//! Everything in this crate is off, or set to its most conservative default, until it's turned
+//! on. `install` turns on what an application usually wants, reading overrides from the
+//! environment so they can be changed without a rebuild:
+//!
+//! - A panic hook that prints panics as a short report, with a backtrace if `RUST_BACKTRACE` asks
+//!   for one, and records their location for `panic::catch_traced`.
+//! - Hyperlinks from printed frames to their source, if the terminal looks like it supports them.
+//! - Colored output, if stderr is a terminal, unless `NO_COLOR` is set or `TERM` is `dumb`.
+//!   `FORCE_COLOR` turns colors on or off regardless.
+//!
+//! Colors apply to traced errors as well as panics: with colors on, the headings of a
+//! `TracedReport` are bold and the frame indices of a trace are dimmed. Traced errors returned
+//! from `main` still print through their `Debug` impl, so they pick the colors up as is.
+//!
+//! | Variable                      | Values                                   | Default   |
+//! |-------------------------------|------------------------------------------|-----------|
+//! | `TRIAL_AND_ERROR_MODE`        | `full`, `counters`                       | `full`    |
+//! | `TRIAL_AND_ERROR_DENYLIST`    | comma separated path patterns            | none      |
+//! | `TRIAL_AND_ERROR_HYPERLINKS`  | `never`, `auto`, `always`                | `auto`    |
+//! | `TRIAL_AND_ERROR_LINKS`       | `file`, `vscode`, `idea`, or a template  | `file`    |
+//! | `TRIAL_AND_ERROR_UNHANDLED`   | `1` to warn about unhandled errors       | off       |
+//! | `TRIAL_AND_ERROR_HOP_TIMING`  | `1` to time every frame                  | off       |
+//!
+//! Applications that want a different setup can build their own `Config` and `apply` it.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::{Ok, Result, TracedReport};
+//!
+//! fn main() -> Result<(), TracedReport> {
+//!     trial_and_error::install()?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::panic::{self as std_panic, PanicInfo};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::age::set_hop_timing;
+use super::hyperlink::{set_hyperlinks, Hyperlinks, LinkScheme};
+use super::unhandled::set_unhandled_detection;
+use super::{panic, set_frame_denylist, set_trace_mode, TraceMode};
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+// Colors are off unless turned on, so output that isn't going to a terminal stays plain
+static COLORS: AtomicBool = AtomicBool::new(false);
+
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// Turn colored output on or off.
+pub fn set_colors(enabled: bool) {
+    COLORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether output is colored.
+pub fn colors() -> bool {
+    COLORS.load(Ordering::Relaxed)
+}
+
+/// Text that's written in a style if colors are on, and plainly otherwise.
+pub(crate) struct Painted<T> {
+    style: &'static str,
+    text: T,
+}
+
+/// Paint `text` as a heading.
+pub(crate) fn heading<T: fmt::Display>(text: T) -> Painted<T> {
+    Painted { style: BOLD, text }
+}
+
+/// Paint `text` dimmed, for things printed alongside what matters.
+pub(crate) fn dim<T: fmt::Display>(text: T) -> Painted<T> {
+    Painted { style: DIM, text }
+}
+
+impl<T: fmt::Display> fmt::Display for Painted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if colors() {
+            write!(f, "{}{}{}", self.style, self.text, RESET)
+        } else {
+            fmt::Display::fmt(&self.text, f)
+        }
+    }
+}
+
+/// Error returned by `install` when it has already been called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlreadyInstalled;
+
+impl fmt::Display for AlreadyInstalled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("traced errors were already installed")
+    }
+}
+
+impl Error for AlreadyInstalled {}
+
+/// A setup for an application, as applied by `install`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    mode: TraceMode,
+    denylist: Vec<String>,
+    hyperlinks: Hyperlinks,
+    links: LinkScheme,
+    colors: bool,
+    unhandled: bool,
+    hop_timing: bool,
+    panic_hook: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            mode: TraceMode::Full,
+            denylist: Vec::new(),
+            hyperlinks: Hyperlinks::Auto,
+            links: LinkScheme::File,
+            colors: false,
+            unhandled: false,
+            hop_timing: false,
+            panic_hook: true,
+        }
+    }
+}
+
+impl Config {
+    /// Create the default setup, without looking at the environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the default setup, overridden by the environment variables of the process.
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    /// Create the default setup, overridden by the variables `lookup` finds.
+    ///
+    /// Values that aren't understood are ignored, leaving the default in place.
+    pub fn from_lookup<F>(lookup: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut config = Config::default();
+
+        if let Some(mode) = lookup("TRIAL_AND_ERROR_MODE") {
+            match mode.as_str() {
+                "full" => config.mode = TraceMode::Full,
+                "counters" => config.mode = TraceMode::Counters,
+                _ => {}
+            }
+        }
+
+        if let Some(patterns) = lookup("TRIAL_AND_ERROR_DENYLIST") {
+            config.denylist = patterns
+                .split(',')
+                .map(str::trim)
+                .filter(|pattern| !pattern.is_empty())
+                .map(String::from)
+                .collect();
+        }
+
+        if let Some(when) = lookup("TRIAL_AND_ERROR_HYPERLINKS") {
+            match when.as_str() {
+                "never" => config.hyperlinks = Hyperlinks::Never,
+                "auto" => config.hyperlinks = Hyperlinks::Auto,
+                "always" => config.hyperlinks = Hyperlinks::Always,
+                _ => {}
+            }
+        }
+
+        if let Some(scheme) = lookup("TRIAL_AND_ERROR_LINKS") {
+            match scheme.as_str() {
+                "file" => config.links = LinkScheme::File,
+                "vscode" => config.links = LinkScheme::Vscode,
+                "idea" => config.links = LinkScheme::Idea,
+                template if template.contains("{path}") => {
+                    config.links = LinkScheme::Custom(template.to_string())
+                }
+                _ => {}
+            }
+        }
+
+        config.colors = detect_colors(&lookup, stderr_is_terminal());
+        config.unhandled = lookup("TRIAL_AND_ERROR_UNHANDLED").as_deref() == Some("1");
+        config.hop_timing = lookup("TRIAL_AND_ERROR_HOP_TIMING").as_deref() == Some("1");
+
+        config
+    }
+
+    /// Choose what propagating an error records.
+    pub fn mode(mut self, mode: TraceMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Drop frames in files matching any of `patterns`; see `set_frame_denylist`.
+    pub fn denylist<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.denylist = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Choose when printed frames link to their source, and what they link to.
+    pub fn hyperlinks(mut self, when: Hyperlinks, scheme: LinkScheme) -> Self {
+        self.hyperlinks = when;
+        self.links = scheme;
+        self
+    }
+
+    /// Turn colored output on or off.
+    pub fn colors(mut self, enabled: bool) -> Self {
+        self.colors = enabled;
+        self
+    }
+
+    /// Warn about traced errors dropped without being handled; see the `unhandled` module.
+    pub fn unhandled_detection(mut self, enabled: bool) -> Self {
+        self.unhandled = enabled;
+        self
+    }
+
+    /// Record when every frame was recorded; see the `age` module.
+    pub fn hop_timing(mut self, enabled: bool) -> Self {
+        self.hop_timing = enabled;
+        self
+    }
+
+    /// Install the panic hook, or leave the current one in place.
+    pub fn panic_hook(mut self, enabled: bool) -> Self {
+        self.panic_hook = enabled;
+        self
+    }
+
+    /// Apply the setup, replacing whatever was set before.
+    pub fn apply(self) {
+        set_trace_mode(self.mode);
+        set_frame_denylist(self.denylist);
+        set_hyperlinks(self.hyperlinks, self.links);
+        set_colors(self.colors);
+        set_unhandled_detection(self.unhandled);
+        set_hop_timing(self.hop_timing);
+
+        if self.panic_hook {
+            std_panic::set_hook(Box::new(report_panic));
+        }
+    }
+}
+
+/// Guess whether output should be colored from the environment, and whether stderr is a terminal.
+fn detect_colors<F>(lookup: &F, terminal: bool) -> bool
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(force) = lookup("FORCE_COLOR") {
+        return force != "0";
+    }
+
+    terminal && lookup("NO_COLOR").is_none() && lookup("TERM").as_deref() != Some("dumb")
+}
+
+#[cfg(unix)]
+fn stderr_is_terminal() -> bool {
+    extern "C" {
+        fn isatty(fd: i32) -> i32;
+    }
+
+    // `isatty` only reads the file descriptor it's given, and any libc std links against has it
+    unsafe { isatty(2) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stderr_is_terminal() -> bool {
+    false
+}
+
+/// Set up traced errors for an application, with overrides from the environment.
+///
+/// This applies `Config::from_env`. It can only be called once; later calls change nothing and
+/// return an error.
+pub fn install() -> std::result::Result<(), AlreadyInstalled> {
+    if INSTALLED.swap(true, Ordering::AcqRel) {
+        return std::result::Result::Err(AlreadyInstalled);
+    }
+
+    Config::from_env().apply();
+    std::result::Result::Ok(())
+}
+
+/// Panic hook installed by `install`.
+fn report_panic(info: &PanicInfo<'_>) {
+    panic::record_location(info);
+
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(message) => Some(*message),
+        None => info.payload().downcast_ref::<String>().map(String::as_str),
+    };
+    let (red, reset) = if colors() { (RED, RESET) } else { ("", "") };
+
+    // Writing to a `String` can't fail
+    let mut report = format!("{}The application panicked{}", red, reset);
+    if let Some(message) = message {
+        let _ = write!(report, ": {}", message);
+    }
+    if let Some(location) = info.location() {
+        let _ = write!(report, "\n    at {}", location);
+    }
+
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        let _ = write!(report, "\n\nBacktrace:\n{}", backtrace);
+    } else {
+        report.push_str("\n\nRun with RUST_BACKTRACE=1 to include a backtrace.");
+    }
+
+    eprintln!("{}", report);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn with_vars(vars: &[(&str, &str)]) -> Config {
+        let vars: HashMap<_, _> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        Config::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn defaults_apply_without_variables() {
+        let config = with_vars(&[]);
+
+        assert_eq!(TraceMode::Full, config.mode);
+        assert_eq!(Hyperlinks::Auto, config.hyperlinks);
+        assert!(!config.unhandled);
+        assert!(config.panic_hook);
+    }
+
+    #[test]
+    fn variables_override_defaults() {
+        let config = with_vars(&[
+            ("TRIAL_AND_ERROR_MODE", "counters"),
+            ("TRIAL_AND_ERROR_DENYLIST", "*/vendor/*, ,*/generated/*"),
+            ("TRIAL_AND_ERROR_HYPERLINKS", "always"),
+            ("TRIAL_AND_ERROR_LINKS", "editor://{path}:{line}"),
+            ("TRIAL_AND_ERROR_UNHANDLED", "1"),
+            ("NO_COLOR", ""),
+        ]);
+
+        assert_eq!(TraceMode::Counters, config.mode);
+        assert_eq!(vec!["*/vendor/*", "*/generated/*"], config.denylist);
+        assert_eq!(Hyperlinks::Always, config.hyperlinks);
+        assert!(matches!(config.links, LinkScheme::Custom(_)));
+        assert!(config.unhandled);
+        assert!(!config.hop_timing);
+
+        let ignored = with_vars(&[("TRIAL_AND_ERROR_MODE", "sometimes")]);
+        assert_eq!(TraceMode::Full, ignored.mode);
+    }
+
+    #[test]
+    fn colors_need_a_terminal_unless_forced() {
+        let colors = |vars: &[(&str, &str)], terminal| {
+            detect_colors(
+                &|key: &str| {
+                    vars.iter()
+                        .find(|(name, _)| *name == key)
+                        .map(|(_, value)| value.to_string())
+                },
+                terminal,
+            )
+        };
+
+        assert!(colors(&[], true));
+        assert!(!colors(&[], false));
+        assert!(!colors(&[("NO_COLOR", "")], true));
+        assert!(!colors(&[("TERM", "dumb")], true));
+        assert!(colors(&[("FORCE_COLOR", "1")], false));
+        assert!(!colors(&[("FORCE_COLOR", "0")], true));
+    }
+}
@@ -0,0 +1,230 @@
+//! A place for long-running loops to report errors they recover from.
+//!
+//! The main loop of a GUI, game, or daemon can't give up on the first error, so errors usually
+//! end up printed with `eprintln!` and forgotten, or printed once per frame until the log is
+//! useless. An `ErrorSink` takes them instead. Errors propagated along the same path are grouped
+//! by their `Fingerprint`, so a repeat only bumps a counter, while the first of each group is
+//! handed to the boundary hook registered with `set_boundary_hook`. The most recent groups are
+//! kept, newest first, for the loop to display however it likes.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::sink::ErrorSink;
+//! use trial_and_error::return_trace::{Err, Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let mut sink = ErrorSink::new(16);
+//!
+//! for input in ["1", "two", "three", "4"] {
+//!     if let Err(error) = parse(input) {
+//!         sink.report(error);
+//!     }
+//! }
+//!
+//! let recent: Vec<_> = sink.recent().collect();
+//!
+//! assert_eq!(1, recent.len());
+//! assert_eq!(2, recent[0].count());
+//! ```
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::time::Instant;
+
+use super::fingerprint::Fingerprint;
+use super::{call_boundary_hook, trace_log_of, MaybeTraced, TraceLog, TracedReport};
+
+/// A group of errors propagated along the same path, as kept by an `ErrorSink`.
+#[derive(Debug)]
+pub struct SinkEntry {
+    /// The most recent error of the group.
+    error: TracedReport,
+    fingerprint: Fingerprint,
+    /// The number of errors reported in the group.
+    count: u64,
+    first_seen: Instant,
+    last_seen: Instant,
+}
+
+impl SinkEntry {
+    /// The most recent error of the group.
+    pub fn error(&self) -> &TracedReport {
+        &self.error
+    }
+
+    /// The fingerprint shared by the errors of the group.
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    /// The number of errors reported in the group.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// When the first error of the group was reported.
+    pub fn first_seen(&self) -> Instant {
+        self.first_seen
+    }
+
+    /// When the most recent error of the group was reported.
+    pub fn last_seen(&self) -> Instant {
+        self.last_seen
+    }
+}
+
+/// Groups reported errors by fingerprint, keeping the most recently reported groups.
+#[derive(Debug)]
+pub struct ErrorSink {
+    /// The kept groups, most recently reported first.
+    entries: VecDeque<SinkEntry>,
+    /// The number of groups to keep.
+    capacity: usize,
+    /// The number of errors reported in groups that are no longer kept.
+    evicted: u64,
+}
+
+impl ErrorSink {
+    /// Create a new, empty `ErrorSink` that keeps the `capacity` most recently reported groups.
+    pub fn new(capacity: usize) -> Self {
+        ErrorSink {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            evicted: 0,
+        }
+    }
+
+    /// Report an error, handing its trace to the boundary hook if it's the first of its group.
+    ///
+    /// The error is grouped by the trace it had when it was reported, and then converted into a
+    /// `TracedReport`, recording the call site as a "converted here" frame. Returns whether it was
+    /// the first of its group.
+    #[track_caller]
+    pub fn report<E>(&mut self, error: E) -> bool
+    where
+        E: Error + MaybeTraced + Send + Sync + 'static,
+    {
+        let fingerprint = trace_log_of(&error)
+            .unwrap_or(&TraceLog::new())
+            .fingerprint();
+
+        self.insert(TracedReport::new(error), fingerprint)
+    }
+
+    /// Report an error that is already a `TracedReport`; see `report`.
+    pub fn report_erased(&mut self, error: TracedReport) -> bool {
+        let fingerprint = error.trace_log().fingerprint();
+        self.insert(error, fingerprint)
+    }
+
+    fn insert(&mut self, error: TracedReport, fingerprint: Fingerprint) -> bool {
+        let now = Instant::now();
+        error.mark_handled();
+
+        if let Some(index) = self
+            .entries
+            .iter()
+            .position(|entry| entry.fingerprint == fingerprint)
+        {
+            let mut entry = self.entries.remove(index).expect("index was just found");
+            entry.error = error;
+            entry.count += 1;
+            entry.last_seen = now;
+            self.entries.push_front(entry);
+
+            return false;
+        }
+
+        call_boundary_hook(error.trace_log());
+
+        if self.entries.len() >= self.capacity {
+            if let Some(entry) = self.entries.pop_back() {
+                self.evicted += entry.count;
+            }
+        }
+
+        if self.capacity > 0 {
+            self.entries.push_front(SinkEntry {
+                error,
+                fingerprint,
+                count: 1,
+                first_seen: now,
+                last_seen: now,
+            });
+        }
+
+        true
+    }
+
+    /// The kept groups, most recently reported first.
+    pub fn recent(&self) -> impl Iterator<Item = &SinkEntry> + '_ {
+        self.entries.iter()
+    }
+
+    /// The total number of errors reported, including those in groups no longer kept.
+    pub fn total(&self) -> u64 {
+        self.evicted + self.entries.iter().map(SinkEntry::count).sum::<u64>()
+    }
+
+    /// Forget every group, for example once the user has dismissed them.
+    ///
+    /// Errors reported afterwards are handed to the boundary hook again, even if they were seen
+    /// before.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Err, IntoTraced, MissingValue, Result, TracedError};
+
+    fn first() -> Result<(), TracedError<MissingValue>> {
+        Err(MissingValue::of::<u8>().into_traced())
+    }
+
+    fn second() -> Result<(), TracedError<MissingValue>> {
+        Err(MissingValue::of::<u16>().into_traced())
+    }
+
+    #[test]
+    fn repeats_are_grouped_and_moved_to_the_front() {
+        let mut sink = ErrorSink::new(4);
+
+        assert!(sink.report(first().unwrap_err()));
+        assert!(sink.report(second().unwrap_err()));
+        assert!(!sink.report(first().unwrap_err()));
+
+        let recent: Vec<_> = sink.recent().collect();
+        assert_eq!(2, recent.len());
+        assert_eq!(2, recent[0].count());
+        assert_eq!(1, recent[1].count());
+        assert!(recent[0].last_seen() >= recent[0].first_seen());
+        assert_eq!(3, sink.total());
+    }
+
+    #[test]
+    fn oldest_groups_are_evicted() {
+        let mut sink = ErrorSink::new(1);
+
+        sink.report(first().unwrap_err());
+        sink.report(first().unwrap_err());
+        sink.report(second().unwrap_err());
+
+        let recent: Vec<_> = sink.recent().collect();
+        assert_eq!(1, recent.len());
+        assert!(recent[0].error().to_string().contains("u16"));
+        assert_eq!(3, sink.total());
+
+        sink.clear();
+        let report = TracedReport::new(second().unwrap_err());
+        assert!(sink.report_erased(report));
+    }
+}
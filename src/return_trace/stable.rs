@@ -0,0 +1,154 @@
+//! Autoref-based dispatch that picks the traced path without specialization.
+//!
+//! Propagating with `?` relies on `min_specialization` to only record locations for errors that
+//! implement `Traced`, and on the unstable `Try` trait for the traced `Result` itself. The
+//! `traced!` macro is an alternative to `?` for `std::result::Result` that makes the same choice
+//! with autoref specialization instead, which only relies on method resolution and works with a
+//! stable toolchain.
+//!
+//! The macro records the location on the error _before_ converting it into the function's error
+//! type, so an error needs to be traced already (e.g. a `TracedError`) for its trace to grow.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::IntoTraced;
+//! use trial_and_error::{traced, TracedError};
+//!
+//! #[derive(Debug)]
+//! struct ParseError;
+//! # impl std::fmt::Display for ParseError {
+//! #     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+//! #         f.write_str("ParseError")
+//! #     }
+//! # }
+//! # impl std::error::Error for ParseError {}
+//!
+//! fn parse() -> Result<u32, TracedError<ParseError>> {
+//!     Err(ParseError.into_traced())
+//! }
+//!
+//! fn run() -> Result<u32, TracedError<ParseError>> {
+//!     let value = traced!(parse());
+//!
+//!     Ok(value)
+//! }
+//!
+//! assert_eq!(2, run().unwrap_err().trace_log().len());
+//! ```
+
+use std::panic::Location;
+
+use super::Traced;
+
+/// Wrapper used by `traced!` to select a dispatch trait with method resolution.
+#[doc(hidden)]
+pub struct Dispatch<'a, E>(pub &'a mut E);
+
+/// Selected by `traced!` for errors that implement `Traced`.
+#[doc(hidden)]
+pub trait TracedDispatch {
+    fn record_location(self, location: &'static Location<'static>);
+}
+
+impl<E> TracedDispatch for &mut Dispatch<'_, E>
+where
+    E: Traced,
+{
+    fn record_location(self, location: &'static Location<'static>) {
+        if !super::is_denylisted(location) {
+            self.0.trace(location);
+        }
+    }
+}
+
+/// Selected by `traced!` for every other error, only after autoderef has ruled out
+/// `TracedDispatch`.
+#[doc(hidden)]
+pub trait UntracedDispatch {
+    fn record_location(&self, location: &'static Location<'static>);
+}
+
+impl<E> UntracedDispatch for Dispatch<'_, E> {
+    fn record_location(&self, _location: &'static Location<'static>) {}
+}
+
+/// Unwrap a `std::result::Result` like `?`, recording the location on errors that implement
+/// `Traced` before converting and returning them.
+#[macro_export]
+macro_rules! traced {
+    ($result:expr) => {
+        match $result {
+            ::std::result::Result::Ok(value) => value,
+            ::std::result::Result::Err(mut error) => {
+                #[allow(unused_imports)]
+                use $crate::return_trace::stable::{TracedDispatch, UntracedDispatch};
+
+                // The `&mut` is what makes `TracedDispatch` win over `UntracedDispatch`
+                #[allow(clippy::unnecessary_mut_passed)]
+                let () = (&mut $crate::return_trace::stable::Dispatch(&mut error))
+                    .record_location(::std::panic::Location::caller());
+
+                return ::std::result::Result::Err(::std::convert::From::from(error));
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::return_trace::{IntoTraced, TracedError};
+    use crate::TracedReport;
+
+    #[derive(Debug)]
+    struct PlainError;
+
+    impl std::fmt::Display for PlainError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("PlainError is here!")
+        }
+    }
+
+    impl std::error::Error for PlainError {}
+
+    #[cfg(feature = "dyn-dispatch")]
+    impl crate::return_trace::MaybeTraced for PlainError {}
+
+    fn traced_source() -> Result<(), TracedError<PlainError>> {
+        Err(PlainError.into_traced())
+    }
+
+    #[test]
+    fn traced_errors_record_macro_site() {
+        fn caller() -> Result<(), TracedError<PlainError>> {
+            traced!(traced_source());
+            Ok(())
+        }
+
+        let error = caller().unwrap_err();
+        let frames = error.trace_log().frames();
+
+        assert_eq!(2, frames.len());
+        assert_eq!(file!(), frames[1].location().file());
+    }
+
+    #[test]
+    fn untraced_errors_are_converted_unchanged() {
+        fn caller() -> Result<(), TracedReport> {
+            traced!(Err(PlainError));
+            Ok(())
+        }
+
+        let report = caller().unwrap_err();
+
+        assert!(report.trace_log().is_empty());
+        assert_eq!("PlainError is here!", report.to_string());
+    }
+
+    #[test]
+    fn ok_values_are_unwrapped() {
+        fn caller() -> Result<u32, PlainError> {
+            Ok(traced!(Ok::<_, PlainError>(4)))
+        }
+
+        assert_eq!(4, caller().unwrap());
+    }
+}
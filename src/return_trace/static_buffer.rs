@@ -0,0 +1,187 @@
+//! Allocation-free trace storage in a `static` buffer.
+//!
+//! `TraceLog` stores its frames in a `Vec`, which isn't available on targets without an
+//! allocator. `StaticTraceBuffer` instead writes frames into a fixed size ring buffer that lives
+//! in a `static`, using an atomic cursor so it can be shared between interrupt handlers and tasks
+//! without a lock. Error types on such targets implement `Traced` by pushing into the buffer, and
+//! the most recent frames can be dumped over a debug channel after a fault.
+//!
+//! Pushing into the buffer never allocates or takes a lock, so it's safe from contexts where
+//! neither is allowed, but the crate as a whole still depends on `std`.
+//!
+//! ```rust
+//! #![feature(min_specialization)]
+//! use core::panic::Location;
+//! use trial_and_error::return_trace::static_buffer::StaticTraceBuffer;
+//! use trial_and_error::return_trace::{Err, Ok, Result};
+//! use trial_and_error::Traced;
+//!
+//! static TRACE: StaticTraceBuffer<32> = StaticTraceBuffer::new();
+//!
+//! #[derive(Debug)]
+//! struct SensorTimeout;
+//!
+//! impl Traced for SensorTimeout {
+//!     fn trace(&mut self, location: &'static Location<'static>) {
+//!         TRACE.push(location);
+//!     }
+//! }
+//!
+//! fn read_sensor() -> Result<u16, SensorTimeout> {
+//!     Err(SensorTimeout)
+//! }
+//!
+//! fn poll() -> Result<u16, SensorTimeout> {
+//!     let value = read_sensor()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! assert!(poll().is_err());
+//!
+//! for location in TRACE.frames() {
+//!     // write `location` to the debug channel
+//! #   assert_eq!(file!(), location.file());
+//! }
+//! ```
+
+use core::panic::Location;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+/// Fixed size ring buffer of propagation locations that can be stored in a `static`.
+///
+/// Once more than `N` frames have been pushed, the oldest frames are overwritten, so the buffer
+/// always holds the `N` most recent frames.
+pub struct StaticTraceBuffer<const N: usize> {
+    /// The recorded locations, null for slots that haven't been written yet.
+    slots: [AtomicPtr<Location<'static>>; N],
+    /// The total number of frames ever pushed.
+    cursor: AtomicUsize,
+}
+
+// Used to initialize `slots`, since array repeat expressions need a constant for non-`Copy` types
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: AtomicPtr<Location<'static>> = AtomicPtr::new(ptr::null_mut());
+
+impl<const N: usize> StaticTraceBuffer<N> {
+    /// Create a new, empty buffer.
+    pub const fn new() -> Self {
+        StaticTraceBuffer {
+            slots: [EMPTY_SLOT; N],
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    /// Record a new propagation location, overwriting the oldest one if the buffer is full.
+    #[inline]
+    pub fn push(&self, location: &'static Location<'static>) {
+        let ind = self.cursor.fetch_add(1, Ordering::AcqRel);
+
+        if N > 0 {
+            let location = location as *const Location<'static> as *mut Location<'static>;
+            self.slots[ind % N].store(location, Ordering::Release);
+        }
+    }
+
+    /// Iterate over the recorded locations, oldest first.
+    ///
+    /// Frames pushed concurrently with iteration may or may not be observed, so this is meant to
+    /// be called once whatever was propagating errors has stopped, e.g. from a fault handler.
+    pub fn frames(&self) -> impl Iterator<Item = &'static Location<'static>> + '_ {
+        // The cursor is loaded once, since a concurrent push could otherwise make the length
+        // outgrow the total it's subtracted from
+        let total = self.total();
+        let start = total - total.min(N);
+
+        (start..total).filter_map(move |ind| {
+            let location = self.slots[ind % N].load(Ordering::Acquire);
+
+            // SAFETY: non-null slots are only ever written by `push`, from a
+            // `&'static Location<'static>`
+            unsafe { location.as_ref() }
+        })
+    }
+
+    /// The number of frames currently held by the buffer.
+    pub fn len(&self) -> usize {
+        self.total().min(N)
+    }
+
+    /// Whether no frames have been pushed since the buffer was created or cleared.
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// The number of frames that were overwritten because the buffer was full.
+    pub fn dropped(&self) -> usize {
+        self.total() - self.len()
+    }
+
+    /// Forget every recorded frame.
+    pub fn clear(&self) {
+        self.cursor.store(0, Ordering::Release);
+
+        for slot in &self.slots {
+            slot.store(ptr::null_mut(), Ordering::Release);
+        }
+    }
+
+    /// The total number of frames pushed since the buffer was created or cleared.
+    fn total(&self) -> usize {
+        self.cursor.load(Ordering::Acquire)
+    }
+}
+
+impl<const N: usize> Default for StaticTraceBuffer<N> {
+    fn default() -> Self {
+        StaticTraceBuffer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn here() -> &'static Location<'static> {
+        Location::caller()
+    }
+
+    #[test]
+    fn buffer_keeps_most_recent_frames_in_order() {
+        static BUFFER: StaticTraceBuffer<4> = StaticTraceBuffer::new();
+
+        // Every call site has a column of its own, so the frames only match if they come back in
+        // the order they were pushed
+        let locations = [here(), here(), here(), here(), here(), here()];
+        let mut columns: Vec<_> = locations.iter().map(|location| location.column()).collect();
+        columns.dedup();
+        assert_eq!(locations.len(), columns.len());
+
+        for location in &locations {
+            BUFFER.push(location);
+        }
+
+        let frames: Vec<_> = BUFFER.frames().collect();
+
+        assert_eq!(&locations[2..], &frames[..]);
+        assert_eq!(4, BUFFER.len());
+        assert_eq!(2, BUFFER.dropped());
+
+        BUFFER.clear();
+
+        assert!(BUFFER.is_empty());
+        assert_eq!(0, BUFFER.frames().count());
+    }
+
+    #[test]
+    fn zero_sized_buffer_only_counts_frames() {
+        let buffer = StaticTraceBuffer::<0>::new();
+
+        buffer.push(here());
+
+        assert_eq!(0, buffer.frames().count());
+        assert_eq!(1, buffer.dropped());
+    }
+}
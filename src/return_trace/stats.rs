@@ -0,0 +1,127 @@
+//! Per-location propagation counters.
+//!
+//! Recording full traces for every error can be too expensive to leave enabled everywhere. When
+//! the trace mode is set to `TraceMode::Counters`, propagating an error with `?` doesn't touch the
+//! error at all, and instead bumps a counter for the location of the `?` in a global table. The
+//! table can be dumped at any point to find out which `?` sites fire most.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::{self, stats, Ok, Result, TraceMode};
+//!
+//! fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! return_trace::set_trace_mode(TraceMode::Counters);
+//!
+//! for _ in 0..3 {
+//!     let _ = parse("nope");
+//! }
+//!
+//! let (location, count) = stats::counts()[0];
+//!
+//! assert_eq!(3, count);
+//! println!("{} fired {} times", location, count);
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::lazy::SyncLazy;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+type CounterTable = HashMap<&'static Location<'static>, AtomicU64>;
+
+static COUNTERS: SyncLazy<RwLock<CounterTable>> = SyncLazy::new(|| RwLock::new(HashMap::new()));
+
+/// Bump the counter for `location`, inserting it into the table if needed.
+pub(crate) fn increment(location: &'static Location<'static>) {
+    {
+        let counters = COUNTERS.read().unwrap_or_else(|e| e.into_inner());
+
+        // Locations that have fired before only need the shared lock
+        if let Some(counter) = counters.get(location) {
+            counter.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    }
+
+    COUNTERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(location)
+        .or_insert_with(|| AtomicU64::new(0))
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// A snapshot of every counted location and how often it fired, most frequent first.
+pub fn counts() -> Vec<(&'static Location<'static>, u64)> {
+    let counters = COUNTERS.read().unwrap_or_else(|e| e.into_inner());
+
+    let mut counts: Vec<_> = counters
+        .iter()
+        .map(|(location, counter)| (*location, counter.load(Ordering::Relaxed)))
+        .collect();
+
+    counts.sort_by(|(a_location, a_count), (b_location, b_count)| {
+        b_count
+            .cmp(a_count)
+            .then_with(|| a_location.file().cmp(b_location.file()))
+            .then_with(|| a_location.line().cmp(&b_location.line()))
+            .then_with(|| a_location.column().cmp(&b_location.column()))
+    });
+
+    counts
+}
+
+/// Render the table as one `count: location` line per location, most frequent first.
+pub fn dump() -> String {
+    let mut output = String::new();
+
+    for (location, count) in counts() {
+        // Writing to a `String` can't fail
+        let _ = writeln!(output, "{:>8}: {}", count, location);
+    }
+
+    output
+}
+
+/// Clear every counter from the table.
+pub fn reset() {
+    COUNTERS.write().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn here() -> &'static Location<'static> {
+        Location::caller()
+    }
+
+    #[test]
+    fn increment_counts_each_location_separately() {
+        let first = here();
+        let second = here();
+
+        increment(first);
+        increment(first);
+        increment(second);
+
+        let counts = counts();
+        let count_of = |location| {
+            counts
+                .iter()
+                .find(|(counted, _)| *counted == location)
+                .map(|(_, count)| *count)
+        };
+
+        assert_eq!(Some(2), count_of(first));
+        assert_eq!(Some(1), count_of(second));
+        assert!(dump().contains(&format!("       2: {}", first)));
+    }
+}
@@ -0,0 +1,331 @@
+//! Choosing where the frames of a `TracedError` are kept.
+//!
+//! `TracedError` is generic over a `TraceStorage`, which decides how frames are stored as the error
+//! is propagated. The default is `TraceLog`, which keeps every frame on the heap. Library authors
+//! who want their errors to stay small or avoid allocating can pick a different storage in the
+//! error type itself:
+//!
+//! - `TraceBuf<N>` keeps the first `N` frames inline and only counts the ones that don't fit.
+//! - `InlineTrace<N>` keeps up to `N` frames inline, and moves them to the heap once there are
+//!   more.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::storage::{TraceBuf, TraceStorage};
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! type ParseError = TracedError<ParseIntError, TraceBuf<4>>;
+//!
+//! fn parse(input: &str) -> Result<u32, ParseError> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let error = parse("four").unwrap_err();
+//!
+//! assert_eq!(1, error.storage().frames().len());
+//! ```
+
+use std::backtrace::Backtrace;
+use std::sync::Arc;
+
+use super::{
+    push_flags, unhandled, TraceFrame, TraceLog, HOP_TIMING, MEMORY_ACCOUNTING, UNHANDLED_DETECTION,
+};
+
+/// Storage for the frames of a trace.
+///
+/// Like `Traced`, propagation specializes on this trait, so crates implementing it need to enable
+/// `#![feature(min_specialization)]`, unless the `dyn-dispatch` feature is enabled.
+#[cfg_attr(not(feature = "dyn-dispatch"), rustc_specialization_trait)]
+pub trait TraceStorage {
+    /// Create storage with no frames in it yet.
+    fn empty() -> Self
+    where
+        Self: Sized;
+
+    /// Record a frame at the end of the trace.
+    fn push_frame(&mut self, frame: TraceFrame);
+
+    /// The recorded frames, oldest first.
+    fn frames(&self) -> &[TraceFrame];
+
+    /// The number of frames that were recorded but couldn't be kept.
+    fn dropped(&self) -> usize {
+        0
+    }
+
+    /// Attach the value of a local variable to the next frame recorded; see `capture!`.
+    ///
+    /// Storage that has no room for captured values drops them.
+    fn push_capture(&mut self, _name: &'static str, _value: String) {}
+
+    /// Attach a full backtrace to the next frame recorded; see `deep_trace!`.
+    ///
+    /// Storage that has no room for backtraces drops them.
+    fn push_backtrace(&mut self, _backtrace: Backtrace) {}
+
+    /// The frames as a `TraceLog`, if that's how they are stored.
+    ///
+    /// Traces stored some other way aren't handed to the boundary hook and aren't kept when
+    /// converting into a `TracedReport`.
+    fn as_trace_log(&self) -> Option<&TraceLog> {
+        None
+    }
+}
+
+impl TraceStorage for TraceLog {
+    fn empty() -> Self {
+        TraceLog::new()
+    }
+
+    #[inline]
+    fn push_frame(&mut self, frame: TraceFrame) {
+        let flags = push_flags();
+
+        if flags == 0 {
+            Arc::make_mut(&mut self.frames).push(frame);
+        } else {
+            self.push_frame_with(frame, flags);
+        }
+    }
+
+    fn frames(&self) -> &[TraceFrame] {
+        &self.frames
+    }
+
+    fn push_capture(&mut self, name: &'static str, value: String) {
+        self.capture(name, value);
+    }
+
+    fn push_backtrace(&mut self, backtrace: Backtrace) {
+        TraceLog::push_backtrace(self, backtrace);
+    }
+
+    fn as_trace_log(&self) -> Option<&TraceLog> {
+        Some(self)
+    }
+}
+
+impl TraceLog {
+    /// Record a frame with some of the settings that add work to recording one on.
+    #[cold]
+    #[inline(never)]
+    fn push_frame_with(&mut self, frame: TraceFrame, flags: u8) {
+        if flags & HOP_TIMING != 0 {
+            self.record_hop_time();
+        }
+        if flags & UNHANDLED_DETECTION != 0 {
+            self.watch();
+        }
+
+        Arc::make_mut(&mut self.frames).push(frame);
+
+        if flags & MEMORY_ACCOUNTING != 0 {
+            self.reaccount();
+        }
+    }
+}
+
+/// Fixed capacity storage that keeps the first `N` frames of a trace.
+///
+/// Frames recorded once the buffer is full are dropped, and only counted.
+#[derive(Debug, Clone, Copy)]
+pub struct TraceBuf<const N: usize> {
+    /// The recorded frames; only the first `len` are meaningful.
+    frames: [TraceFrame; N],
+    /// The number of frames kept.
+    len: usize,
+    /// The number of frames that didn't fit.
+    dropped: usize,
+}
+
+impl<const N: usize> Default for TraceBuf<N> {
+    fn default() -> Self {
+        // `TraceFrame` has no empty value, so unused slots hold an arbitrary frame that is never
+        // handed out
+        let unused = TraceFrame::new(std::panic::Location::caller());
+
+        TraceBuf {
+            frames: [unused; N],
+            len: 0,
+            dropped: 0,
+        }
+    }
+}
+
+impl<const N: usize> TraceBuf<N> {
+    /// Create a new, empty `TraceBuf`.
+    pub fn new() -> Self {
+        TraceBuf::default()
+    }
+
+    /// Whether the buffer has no room for more frames.
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+}
+
+impl<const N: usize> TraceStorage for TraceBuf<N> {
+    fn empty() -> Self {
+        TraceBuf::new()
+    }
+
+    #[inline]
+    fn push_frame(&mut self, frame: TraceFrame) {
+        if self.is_full() {
+            self.dropped += 1;
+        } else {
+            // SAFETY: the buffer isn't full, so `len` is less than `N` and in bounds of `frames`
+            unsafe { *self.frames.get_unchecked_mut(self.len) = frame };
+            self.len += 1;
+        }
+    }
+
+    fn frames(&self) -> &[TraceFrame] {
+        &self.frames[..self.len]
+    }
+
+    fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+impl<const N: usize> PartialEq for TraceBuf<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.frames() == other.frames() && self.dropped == other.dropped
+    }
+}
+
+impl<const N: usize> Eq for TraceBuf<N> {}
+
+/// Storage that keeps up to `N` frames inline, moving them to the heap once there are more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InlineTrace<const N: usize> {
+    /// All frames so far fit inline.
+    Inline(TraceBuf<N>),
+    /// The frames outgrew the inline buffer.
+    Spilled(TraceLog),
+}
+
+impl<const N: usize> Default for InlineTrace<N> {
+    fn default() -> Self {
+        InlineTrace::Inline(TraceBuf::new())
+    }
+}
+
+impl<const N: usize> TraceStorage for InlineTrace<N> {
+    fn empty() -> Self {
+        InlineTrace::default()
+    }
+
+    #[inline]
+    fn push_frame(&mut self, frame: TraceFrame) {
+        match self {
+            InlineTrace::Inline(buf) if buf.is_full() => {
+                let mut frames = Vec::with_capacity(N * 2);
+                frames.extend_from_slice(buf.frames());
+                frames.push(frame);
+
+                *self = InlineTrace::Spilled(TraceLog {
+                    frames: Arc::new(frames),
+                    captures: None,
+                    deep_frames: None,
+                    hop_times: None,
+                    watch: unhandled::unwatched(),
+                    accounted: 0,
+                });
+                if let InlineTrace::Spilled(log) = self {
+                    log.account();
+                }
+            }
+            InlineTrace::Inline(buf) => buf.push_frame(frame),
+            InlineTrace::Spilled(log) => log.push_frame(frame),
+        }
+    }
+
+    fn frames(&self) -> &[TraceFrame] {
+        match self {
+            InlineTrace::Inline(buf) => buf.frames(),
+            InlineTrace::Spilled(log) => log.frames(),
+        }
+    }
+
+    fn push_capture(&mut self, name: &'static str, value: String) {
+        if let InlineTrace::Spilled(log) = self {
+            log.capture(name, value);
+        }
+    }
+
+    fn push_backtrace(&mut self, backtrace: Backtrace) {
+        if let InlineTrace::Spilled(log) = self {
+            log.push_backtrace(backtrace);
+        }
+    }
+
+    fn as_trace_log(&self) -> Option<&TraceLog> {
+        match self {
+            InlineTrace::Inline(_) => None,
+            InlineTrace::Spilled(log) => Some(log),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+
+    use crate::return_trace::{Ok, Result, TracedError};
+
+    #[derive(Debug, PartialEq)]
+    struct Failed;
+
+    /// Fails with a trace of `depth + 1` frames.
+    fn fail<S: TraceStorage>(depth: usize) -> Result<(), TracedError<Failed, S>> {
+        if depth == 0 {
+            std::result::Result::Err(Failed)?;
+        } else {
+            fail::<S>(depth - 1)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn trace_buf_keeps_first_frames_and_counts_the_rest() {
+        let mut buf = TraceBuf::<2>::new();
+        let locations = [Location::caller(), Location::caller(), Location::caller()];
+
+        for location in locations {
+            buf.push_frame(TraceFrame::new(location));
+        }
+
+        assert!(buf.is_full());
+        assert_eq!(2, buf.frames().len());
+        assert_eq!(buf.frames()[0].location(), locations[0]);
+        assert_eq!(1, buf.dropped());
+    }
+
+    #[test]
+    fn inline_trace_spills_to_heap() {
+        let error = fail::<InlineTrace<2>>(1).unwrap_err();
+        assert!(matches!(error.storage(), InlineTrace::Inline(_)));
+        assert_eq!(2, error.storage().frames().len());
+
+        let error = fail::<InlineTrace<2>>(2).unwrap_err();
+        assert!(matches!(error.storage(), InlineTrace::Spilled(_)));
+        assert_eq!(3, error.storage().frames().len());
+    }
+
+    #[test]
+    fn traced_error_records_into_chosen_storage() {
+        let error = fail::<TraceBuf<1>>(2).unwrap_err();
+
+        assert_eq!(1, error.storage().frames().len());
+        assert_eq!(2, error.storage().dropped());
+        assert_eq!(&Failed, error.inner());
+    }
+}
@@ -0,0 +1,320 @@
+//! Rendering the creation backtrace and the return trace of an error as one timeline.
+//!
+//! An error that captured a `Backtrace` when it was created, and then recorded a trace as it was
+//! propagated, knows its whole history: the stack it was created on, and every hop it took back up
+//! from there. Printed separately, the two overlap and have to be lined up by hand. A
+//! `TimelineFormatter` renders them as one list instead, starting with the stack the error was
+//! created on, innermost call first, up to the function of the first `?`, followed by every frame
+//! of the trace with the values and backtraces captured for it.
+//!
+//! The creation backtrace is the one of the innermost error in the `source` chain that has one.
+//! Errors without a backtrace render as their trace alone.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::timeline::TimelineFormatter;
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let timeline = TimelineFormatter::new().render(&parse("four").unwrap_err());
+//!
+//! assert!(timeline.starts_with("invalid digit found in string\n\nTimeline:\n     0: "));
+//! ```
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+use super::capture::Capture;
+use super::visit::{TraceVisitor, Visit};
+use super::TraceFrame;
+
+/// Renders traced errors as a single timeline of their creation backtrace and return trace.
+#[derive(Debug, Clone)]
+pub struct TimelineFormatter {
+    /// Whether the creation backtrace is cut at the frame of the first `?`.
+    trim_origin: bool,
+}
+
+impl Default for TimelineFormatter {
+    fn default() -> Self {
+        TimelineFormatter { trim_origin: true }
+    }
+}
+
+impl TimelineFormatter {
+    /// Create a new `TimelineFormatter` that cuts the creation backtrace where the trace takes
+    /// over.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show the whole creation backtrace, including the outer calls the error was later
+    /// propagated through.
+    pub fn full_origin(mut self, full: bool) -> Self {
+        self.trim_origin = !full;
+        self
+    }
+
+    /// Render a traced error as a timeline.
+    pub fn render<E>(&self, error: &E) -> String
+    where
+        E: Visit + ?Sized,
+    {
+        let mut events = Events::default();
+        error.visit(&mut events);
+
+        let mut out = String::new();
+        // Writing to a `String` can't fail
+        let _ = self.write(&mut out, &events);
+        out
+    }
+
+    fn write(&self, out: &mut String, events: &Events) -> fmt::Result {
+        write!(out, "{}", events.message)?;
+
+        let origin = events.origin.as_deref().map(parse_backtrace);
+        if origin.is_none() && events.frames.is_empty() {
+            return fmt::Result::Ok(());
+        }
+        out.push_str("\n\nTimeline:");
+
+        if let Some(origin) = origin {
+            let first_hop = events.frames.first();
+            let end = origin
+                .iter()
+                .position(|frame| self.trim_origin && first_hop.map_or(false, |hop| frame.is(hop)))
+                .map_or(origin.len(), |index| index + 1);
+
+            for (index, frame) in origin[..end].iter().enumerate() {
+                let label = if index == 0 { "origin" } else { "" };
+                write!(out, "\n{:>6}  {}", label, frame.symbol)?;
+                if let Some(location) = &frame.location {
+                    write!(out, "\n            at {}", location)?;
+                }
+            }
+
+            if end < origin.len() {
+                write!(
+                    out,
+                    "\n        ({} outer frames omitted; the error was propagated through them)",
+                    origin.len() - end
+                )?;
+            }
+        }
+
+        for (index, frame) in events.frames.iter().enumerate() {
+            write!(out, "\n{:>6}: {}", index, frame)?;
+            events.write_attached(out, index)?;
+        }
+        events.write_attached(out, events.frames.len())?;
+
+        fmt::Result::Ok(())
+    }
+}
+
+/// Visitor that collects the pieces of the timeline.
+#[derive(Default)]
+struct Events {
+    message: String,
+    frames: Vec<TraceFrame>,
+    /// Lines to print under a frame, with the index of that frame.
+    attached: Vec<(usize, String)>,
+    /// The creation backtrace, formatted.
+    origin: Option<String>,
+}
+
+impl Events {
+    fn write_attached(&self, out: &mut String, index: usize) -> fmt::Result {
+        for (_, line) in self.attached.iter().filter(|(frame, _)| *frame == index) {
+            write!(out, "\n          {}", line)?;
+        }
+
+        fmt::Result::Ok(())
+    }
+
+    fn record_origin(&mut self, error: &(dyn Error + 'static)) {
+        if let Some(backtrace) = error.backtrace() {
+            if backtrace.status() == BacktraceStatus::Captured {
+                self.origin = Some(backtrace.to_string());
+            }
+        }
+    }
+}
+
+impl TraceVisitor for Events {
+    fn visit_frame(&mut self, _index: usize, frame: &TraceFrame) {
+        self.frames.push(*frame);
+    }
+
+    fn visit_capture(&mut self, frame: usize, capture: &Capture) {
+        let line = format!("{} = {}", capture.name(), capture.value());
+        self.attached.push((frame, line));
+    }
+
+    fn visit_backtrace(&mut self, frame: usize, backtrace: &Backtrace) {
+        self.attached.push((frame, "backtrace:".to_string()));
+        for line in backtrace.to_string().lines() {
+            self.attached
+                .push((frame, format!("  {}", line.trim_start())));
+        }
+    }
+
+    fn visit_metadata(&mut self, key: &str, value: &dyn fmt::Display) {
+        if key == "message" {
+            self.message = value.to_string();
+        }
+    }
+
+    fn visit_error(&mut self, error: &(dyn Error + 'static)) {
+        self.record_origin(error);
+    }
+
+    fn visit_source(&mut self, _depth: usize, source: &(dyn Error + 'static)) {
+        // Deeper errors are closer to where things went wrong
+        self.record_origin(source);
+    }
+}
+
+/// A frame of a formatted `Backtrace`.
+#[derive(Debug, PartialEq)]
+struct StackFrame {
+    symbol: String,
+    /// The `path:line:column` of the frame, if it was resolved.
+    location: Option<String>,
+}
+
+impl StackFrame {
+    /// Whether this frame is the call the trace frame `hop` was recorded in.
+    fn is(&self, hop: &TraceFrame) -> bool {
+        let location = match &self.location {
+            Some(location) => location,
+            None => return false,
+        };
+
+        let mut parts = location.rsplitn(3, ':');
+        let (_column, line, file) = (parts.next(), parts.next(), parts.next());
+
+        line == Some(&hop.location().line().to_string())
+            && file.map_or(false, |file| file.ends_with(hop.location().file()))
+    }
+}
+
+/// Split a formatted `Backtrace` into its frames, dropping those of the capture itself.
+fn parse_backtrace(backtrace: &str) -> Vec<StackFrame> {
+    let mut frames: Vec<StackFrame> = Vec::new();
+
+    for line in backtrace.lines().map(str::trim) {
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(frame) = frames.last_mut() {
+                frame.location = Some(location.to_string());
+            }
+        } else if let Some((index, symbol)) = line.split_once(": ") {
+            if index.chars().all(|c| c.is_ascii_digit()) {
+                frames.push(StackFrame {
+                    symbol: symbol.to_string(),
+                    location: None,
+                });
+            }
+        }
+    }
+
+    let capture = |frame: &StackFrame| {
+        frame.symbol.starts_with("std::backtrace") || frame.symbol.starts_with("backtrace::")
+    };
+    let skipped = frames.iter().take_while(|frame| capture(frame)).count();
+    frames.drain(..skipped);
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Ok, Result, TracedError};
+
+    #[derive(Debug)]
+    struct Failed(Backtrace);
+
+    impl fmt::Display for Failed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("failed")
+        }
+    }
+
+    impl Error for Failed {
+        fn backtrace(&self) -> Option<&Backtrace> {
+            Some(&self.0)
+        }
+    }
+
+    #[inline(never)]
+    fn fail() -> Result<(), TracedError<Failed>> {
+        std::result::Result::Err(Failed(Backtrace::force_capture()))?;
+        Ok(())
+    }
+
+    fn retry() -> Result<(), TracedError<Failed>> {
+        fail()?;
+        Ok(())
+    }
+
+    #[test]
+    fn origin_runs_into_the_trace() {
+        let timeline = TimelineFormatter::new().render(&retry().unwrap_err());
+        let lines: Vec<_> = timeline.lines().collect();
+
+        assert_eq!("failed", lines[0]);
+        assert_eq!("Timeline:", lines[2]);
+        assert!(lines[3].starts_with("origin  "));
+        assert!(lines[3].contains("timeline::tests::fail"));
+        assert!(timeline.contains("outer frames omitted"));
+
+        let hops: Vec<_> = lines
+            .iter()
+            .filter(|line| line.contains(": src/"))
+            .collect();
+        assert_eq!(2, hops.len());
+        assert!(hops[0].starts_with("     0: "));
+    }
+
+    #[test]
+    fn full_origin_keeps_every_frame() {
+        let error = retry().unwrap_err();
+        let trimmed = TimelineFormatter::new().render(&error);
+        let full = TimelineFormatter::new().full_origin(true).render(&error);
+
+        assert!(full.len() > trimmed.len());
+        assert!(!full.contains("outer frames omitted"));
+    }
+
+    #[test]
+    fn parses_formatted_backtraces() {
+        let frames = parse_backtrace(concat!(
+            "   0: std::backtrace::Backtrace::create\n",
+            "             at library/std/src/backtrace.rs:1\n",
+            "   1: app::run\n",
+            "             at ./src/main.rs:4:9\n",
+            "   2: main\n",
+        ));
+
+        assert_eq!(
+            vec![
+                StackFrame {
+                    symbol: "app::run".to_string(),
+                    location: Some("./src/main.rs:4:9".to_string()),
+                },
+                StackFrame {
+                    symbol: "main".to_string(),
+                    location: None,
+                },
+            ],
+            frames
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Tokio support for traced results.
+//!
+//! Spawning a task with `tokio::spawn` severs the propagation trail of any error the task returns,
+//! since the error travels back through a `JoinHandle` rather than a `?`. `spawn_traced` records
+//! the location it was called from as a frame on any traced error the task returns, so the trace
+//! shows where the task came from.
+//!
+//! Likewise, `TracedSender` wraps a `tokio::sync::mpsc::Sender` of traced errors, recording where
+//! each error was sent across the channel.
+//!
+//! ```rust
+//! use trial_and_error::return_trace::tokio::spawn_traced;
+//! use trial_and_error::return_trace::{Ok, Result, ResultExt, TracedError};
+//!
+//! async fn work() -> Result<u32, TracedError<std::num::ParseIntError>> {
+//!     let value = "nope".parse::<u32>().traced()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+//! let error = runtime.block_on(async {
+//!     let handle = spawn_traced(work());
+//!
+//!     handle.await.unwrap().unwrap_err()
+//! });
+//!
+//! // `.traced()`, the `?` in `work`, and the call to `spawn_traced`
+//! assert_eq!(3, error.trace_log().len());
+//! ```
+
+use std::future::Future;
+use std::panic::Location;
+
+use ::tokio::sync::mpsc::error::SendError;
+use ::tokio::sync::mpsc::Sender;
+use ::tokio::task::JoinHandle;
+
+use super::storage::TraceStorage;
+use super::{record, Err, MaybeTraced, Ok, Result, TraceLog, TracedError};
+
+/// Spawn a new task with `tokio::spawn`, recording the call site on any traced error the task
+/// returns.
+#[track_caller]
+pub fn spawn_traced<F, T, E>(future: F) -> JoinHandle<Result<T, E>>
+where
+    F: Future<Output = Result<T, E>> + Send + 'static,
+    T: Send + 'static,
+    E: MaybeTraced + Send + 'static,
+{
+    let location = Location::caller();
+
+    ::tokio::spawn(async move {
+        match future.await {
+            Ok(value) => Ok(value),
+            Err(mut error) => {
+                record(&mut error, location);
+                Err(error)
+            }
+        }
+    })
+}
+
+/// A `tokio::sync::mpsc::Sender` of traced errors that records every error it sends as having
+/// crossed the channel.
+#[derive(Debug)]
+pub struct TracedSender<E, S = TraceLog> {
+    sender: Sender<TracedError<E, S>>,
+}
+
+impl<E, S> TracedSender<E, S>
+where
+    S: TraceStorage,
+{
+    /// Wrap a `Sender`.
+    pub fn new(sender: Sender<TracedError<E, S>>) -> Self {
+        TracedSender { sender }
+    }
+
+    /// Send an error, recording the call site as the point where it crossed the channel.
+    ///
+    /// The crossing is recorded when this is called, rather than when the returned future
+    /// completes, since futures can't track their caller.
+    #[track_caller]
+    pub fn send(
+        &self,
+        error: TracedError<E, S>,
+    ) -> impl Future<Output = std::result::Result<(), SendError<TracedError<E, S>>>> + '_ {
+        self.sender.send(error.crossed_channel())
+    }
+
+    /// Unwrap the `Sender`.
+    pub fn into_inner(self) -> Sender<TracedError<E, S>> {
+        self.sender
+    }
+}
+
+impl<E, S> Clone for TracedSender<E, S> {
+    fn clone(&self) -> Self {
+        TracedSender {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{FrameKind, IntoTraced};
+
+    #[derive(Debug)]
+    struct TaskError;
+
+    impl std::fmt::Display for TaskError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("TaskError is here!")
+        }
+    }
+
+    impl std::error::Error for TaskError {}
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        ::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn spawn_site_is_recorded_on_task_errors() {
+        let error = block_on(async {
+            let handle = spawn_traced(async { Result::<(), _>::Err(TaskError.into_traced()) });
+            handle.await.unwrap().unwrap_err()
+        });
+        let frames = error.trace_log().frames();
+
+        assert_eq!(2, frames.len());
+        assert_eq!(FrameKind::Propagated, frames[1].kind());
+        assert_eq!(frames[0].location().line(), frames[1].location().line());
+    }
+
+    #[test]
+    fn task_values_are_returned_unchanged() {
+        let value = block_on(async {
+            let handle = spawn_traced(async { Result::<_, TracedError<TaskError>>::Ok(4) });
+            handle.await.unwrap()
+        });
+
+        assert_eq!(Some(4), value.ok());
+    }
+
+    #[test]
+    fn sender_records_channel_crossing() {
+        let error = block_on(async {
+            let (sender, mut receiver) = ::tokio::sync::mpsc::channel(1);
+            let sender = TracedSender::new(sender);
+
+            sender.send(TaskError.into_traced()).await.unwrap();
+            receiver.recv().await.unwrap()
+        });
+        let frames = error.trace_log().frames();
+
+        assert_eq!(2, frames.len());
+        assert_eq!(FrameKind::Sent, frames[1].kind());
+        assert!(frames[1].thread().is_some());
+    }
+}
@@ -0,0 +1,255 @@
+//! Correlating traced errors with distributed traces.
+//!
+//! A trace only follows an error within one process. To line it up with the distributed trace of
+//! the request that caused it, a W3C `traceparent` can be attached to the error with
+//! `TracedError::with_trace_parent`. It stays with the error when it is converted, including into a
+//! `TracedReport`, and is visited as `traceparent` metadata, so it is included wherever the error
+//! is rendered or exported.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::traceparent::TraceParent;
+//! use trial_and_error::return_trace::{Result, ResultExt, TracedError};
+//!
+//! fn parse(input: &str, parent: TraceParent) -> Result<u32, TracedError<ParseIntError>> {
+//!     input
+//!         .parse::<u32>()
+//!         .traced()
+//!         .map_err(|error| error.with_trace_parent(parent))
+//! }
+//!
+//! let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+//! let error = parse("four", header.parse().unwrap()).unwrap_err();
+//!
+//! assert_eq!(header, error.trace_parent().unwrap().to_string());
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::{TracedError, TracedReport};
+
+/// The `sampled` bit of the trace flags.
+const SAMPLED: u8 = 0x01;
+
+/// The position of a traced error in a distributed trace, as carried by a W3C `traceparent`
+/// header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceParent {
+    /// The id of the whole distributed trace.
+    trace_id: u128,
+    /// The id of the span the error was reported from.
+    parent_id: u64,
+    /// The trace flags.
+    flags: u8,
+}
+
+impl TraceParent {
+    /// Create a new, sampled `TraceParent` from a trace id and a span id.
+    ///
+    /// Ids of 0 are invalid according to the W3C specification, but aren't rejected here, so ids
+    /// from other tracing systems can be carried as they are.
+    pub fn new(trace_id: u128, parent_id: u64) -> Self {
+        TraceParent {
+            trace_id,
+            parent_id,
+            flags: SAMPLED,
+        }
+    }
+
+    /// Replace the trace flags.
+    pub fn with_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// The id of the whole distributed trace.
+    pub fn trace_id(&self) -> u128 {
+        self.trace_id
+    }
+
+    /// The id of the span the error was reported from.
+    pub fn parent_id(&self) -> u64 {
+        self.parent_id
+    }
+
+    /// The trace flags.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Whether the caller may have recorded the trace.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+}
+
+/// Formats the `TraceParent` as a version `00` `traceparent` header value.
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+/// Parses a `traceparent` header value.
+///
+/// Values of future versions are accepted as long as they start with the fields of version `00`.
+impl FromStr for TraceParent {
+    type Err = ParseTraceParentError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let mut fields = value.split('-');
+        let mut field = |len| match fields.next() {
+            Some(field)
+                if field.len() == len
+                    && field
+                        .bytes()
+                        .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) =>
+            {
+                std::result::Result::Ok(field)
+            }
+            _ => std::result::Result::Err(ParseTraceParentError(())),
+        };
+
+        let version = field(2)?;
+        let trace_id = field(32)?;
+        let parent_id = field(16)?;
+        let flags = field(2)?;
+
+        let invalid = version == "ff"
+            || (version == "00" && fields.next().is_some())
+            || trace_id.bytes().all(|b| b == b'0')
+            || parent_id.bytes().all(|b| b == b'0');
+        if invalid {
+            return std::result::Result::Err(ParseTraceParentError(()));
+        }
+
+        // Every field was checked to be hex digits of the right length, so these can't fail
+        let hex = |field| u128::from_str_radix(field, 16).map_err(|_| ParseTraceParentError(()));
+
+        std::result::Result::Ok(TraceParent {
+            trace_id: hex(trace_id)?,
+            parent_id: hex(parent_id)? as u64,
+            flags: hex(flags)? as u8,
+        })
+    }
+}
+
+/// Error returned when parsing an invalid `traceparent`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTraceParentError(());
+
+impl fmt::Display for ParseTraceParentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid traceparent")
+    }
+}
+
+impl Error for ParseTraceParentError {}
+
+impl<E, S> TracedError<E, S> {
+    /// Attach `parent` to the error, replacing any `TraceParent` it already had.
+    pub fn with_trace_parent(mut self, parent: TraceParent) -> Self {
+        self.set_trace_parent(parent);
+        self
+    }
+
+    /// Attach `parent` to the error, replacing any `TraceParent` it already had.
+    pub fn set_trace_parent(&mut self, parent: TraceParent) {
+        self.parent = Some(Box::new(parent));
+    }
+
+    /// The `TraceParent` attached to the error, if any.
+    pub fn trace_parent(&self) -> Option<&TraceParent> {
+        self.parent.as_deref()
+    }
+}
+
+impl TracedReport {
+    /// Attach `parent` to the error, replacing any `TraceParent` it already had.
+    pub fn with_trace_parent(mut self, parent: TraceParent) -> Self {
+        self.set_trace_parent(parent);
+        self
+    }
+
+    /// Attach `parent` to the error, replacing any `TraceParent` it already had.
+    pub fn set_trace_parent(&mut self, parent: TraceParent) {
+        self.parent = Some(Box::new(parent));
+    }
+
+    /// The `TraceParent` attached to the error, if any.
+    pub fn trace_parent(&self) -> Option<&TraceParent> {
+        self.parent.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::visit::Collector;
+    use crate::return_trace::{IntoTraced, MissingValue};
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn round_trips_through_header() {
+        let parent: TraceParent = HEADER.parse().unwrap();
+
+        assert_eq!(0x4bf92f3577b34da6a3ce929d0e0e4736, parent.trace_id());
+        assert_eq!(0x00f067aa0ba902b7, parent.parent_id());
+        assert!(parent.is_sampled());
+        assert_eq!(HEADER, parent.to_string());
+
+        let unsampled = TraceParent::new(1, 2).with_flags(0);
+        assert!(!unsampled.is_sampled());
+        assert_eq!(
+            "00-00000000000000000000000000000001-0000000000000002-00",
+            unsampled.to_string()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let invalid = [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        ];
+
+        for header in invalid {
+            assert!(header.parse::<TraceParent>().is_err(), "{}", header);
+        }
+
+        let future = "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra";
+        assert!(future.parse::<TraceParent>().is_ok());
+    }
+
+    #[test]
+    fn parent_is_kept_through_conversion_and_visited() {
+        let parent = TraceParent::new(1, 2);
+        let error = MissingValue::of::<u32>()
+            .into_traced()
+            .with_trace_parent(parent);
+
+        let report = TracedReport::new(error.clone());
+        assert_eq!(Some(&parent), report.trace_parent());
+
+        let converted = error.convert::<Box<dyn Error + Send + Sync>>();
+        assert_eq!(Some(&parent), converted.trace_parent());
+
+        let collected = Collector::collect(&report);
+        assert!(collected
+            .metadata
+            .contains(&("traceparent".to_string(), parent.to_string())));
+    }
+}
@@ -0,0 +1,194 @@
+//! Detecting traced errors that are dropped without ever being handled.
+//!
+//! `let _ = fallible();` and `if let Err(_) = ...` throw errors away without a trace, and so do
+//! the `.ok()`s that were meant to be temporary. With detection turned on with
+//! `set_unhandled_detection`, every `TraceLog` that records a frame is watched, and it warns when
+//! it's dropped before the error holding it was handled, together with the trace of where the
+//! error travelled before it was lost.
+//!
+//! An error counts as handled once it, or its trace, has been displayed or debug printed, visited
+//! by an exporter, handed to the boundary hook or an `ErrorSink`, or unwrapped with `into_inner`.
+//! Errors that are meant to be ignored can say so with `mark_handled`. Errors dropped while the
+//! thread is panicking are not reported, and neither are traces that aren't stored as a
+//! `TraceLog`.
+//!
+//! Warnings go to the hook registered with `set_unhandled_hook`, or to stderr if there is none.
+//! Detection is off by default, and is meant for debug builds and test suites.
+//!
+//! ```rust
+//! use std::num::ParseIntError;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use trial_and_error::return_trace::unhandled::{set_unhandled_detection, set_unhandled_hook};
+//! use trial_and_error::return_trace::{Ok, Result, TracedError};
+//!
+//! static DROPPED: AtomicUsize = AtomicUsize::new(0);
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! set_unhandled_detection(true);
+//! set_unhandled_hook(Box::new(|_trace| {
+//!     DROPPED.fetch_add(1, Ordering::Relaxed);
+//! }));
+//!
+//! let _ = parse("four");
+//! assert_eq!(1, DROPPED.load(Ordering::Relaxed));
+//!
+//! let error = parse("five").unwrap_err();
+//! eprintln!("{}", error);
+//! drop(error);
+//! assert_eq!(1, DROPPED.load(Ordering::Relaxed));
+//! ```
+
+use std::lazy::SyncLazy;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::RwLock;
+use std::thread;
+
+use super::storage::TraceStorage;
+use super::{push_flags, set_push_flag, TraceLog, TracedError, TracedReport, UNHANDLED_DETECTION};
+
+/// The trace has not recorded a frame while detection was on.
+const UNWATCHED: u8 = 0;
+/// The trace will warn when it's dropped.
+const WATCHED: u8 = 1;
+/// The error holding the trace was handled.
+const HANDLED: u8 = 2;
+
+/// Hook called with the trace of every traced error dropped without being handled.
+type UnhandledHook = Box<dyn Fn(&TraceLog) + Send + Sync + 'static>;
+
+static UNHANDLED_HOOK: SyncLazy<RwLock<Option<UnhandledHook>>> =
+    SyncLazy::new(|| RwLock::new(None));
+
+/// Turn detecting traced errors dropped without being handled on or off.
+///
+/// Only traces that record a frame while detection is on are watched.
+pub fn set_unhandled_detection(enabled: bool) {
+    set_push_flag(UNHANDLED_DETECTION, enabled);
+}
+
+/// Whether traced errors dropped without being handled are detected.
+pub fn unhandled_detection() -> bool {
+    push_flags() & UNHANDLED_DETECTION != 0
+}
+
+/// Register a hook that is handed the trace of every traced error dropped without being handled,
+/// instead of printing it to stderr, replacing any previously registered hook.
+pub fn set_unhandled_hook(hook: UnhandledHook) {
+    *UNHANDLED_HOOK.write().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+}
+
+/// Unregister the current unhandled hook, returning it.
+pub fn take_unhandled_hook() -> Option<UnhandledHook> {
+    UNHANDLED_HOOK
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .take()
+}
+
+impl TraceLog {
+    /// Mark the error holding the trace as handled, so it doesn't warn when it's dropped.
+    pub fn mark_handled(&self) {
+        self.watch.store(HANDLED, Ordering::Relaxed);
+    }
+
+    /// Move the watch of the trace over to `to`, a copy that takes its place, so only the copy
+    /// warns if it's dropped unhandled.
+    pub(crate) fn hand_over_watch(&self, to: &mut TraceLog) {
+        *to.watch.get_mut() = self.watch.swap(HANDLED, Ordering::Relaxed);
+    }
+
+    /// Start watching the trace, if it isn't already watched or handled.
+    pub(crate) fn watch(&mut self) {
+        let watch = self.watch.get_mut();
+        if *watch == UNWATCHED {
+            *watch = WATCHED;
+        }
+    }
+
+    /// Warn if the trace is dropped while it's still watched.
+    pub(crate) fn check_handled(&mut self) {
+        if *self.watch.get_mut() == WATCHED && !thread::panicking() {
+            warn_unhandled(self);
+        }
+    }
+}
+
+#[cold]
+fn warn_unhandled(trace: &TraceLog) {
+    let hook = UNHANDLED_HOOK.read().unwrap_or_else(|e| e.into_inner());
+
+    match hook.as_ref() {
+        Some(hook) => hook(trace),
+        None => eprintln!(
+            "warning: a traced error was dropped without being handled\n\nError return trace:\n{}",
+            trace
+        ),
+    }
+}
+
+/// The initial state of the watch of a new trace.
+pub(crate) fn unwatched() -> AtomicU8 {
+    AtomicU8::new(UNWATCHED)
+}
+
+impl<E, S> TracedError<E, S>
+where
+    S: TraceStorage,
+{
+    /// Mark the error as handled, so it doesn't warn when it's dropped; see the `unhandled`
+    /// module.
+    pub fn mark_handled(&self) {
+        if let Some(trace) = self.storage().as_trace_log() {
+            trace.mark_handled();
+        }
+    }
+}
+
+impl TracedReport {
+    /// Mark the error as handled, so it doesn't warn when it's dropped; see the `unhandled`
+    /// module.
+    pub fn mark_handled(&self) {
+        self.trace_log().mark_handled();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::panic::Location;
+
+    /// A trace that is watched, whether or not detection is on.
+    fn watched() -> TraceLog {
+        let mut trace = TraceLog::new();
+        trace.push(Location::caller());
+        *trace.watch.get_mut() = WATCHED;
+        trace
+    }
+
+    #[test]
+    fn displayed_and_marked_traces_are_handled() {
+        let mut displayed = watched();
+        let _ = displayed.to_string();
+        assert_eq!(HANDLED, *displayed.watch.get_mut());
+
+        let mut marked = watched();
+        marked.mark_handled();
+        marked.watch();
+        assert_eq!(HANDLED, *marked.watch.get_mut());
+    }
+
+    #[test]
+    fn clones_are_not_watched() {
+        let trace = watched();
+        let mut clone = trace.clone();
+
+        assert_eq!(UNWATCHED, *clone.watch.get_mut());
+        trace.mark_handled();
+    }
+}
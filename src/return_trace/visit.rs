@@ -0,0 +1,355 @@
+//! Walking a traced error without depending on how its trace is stored.
+//!
+//! Exporters and formatters that pull frames straight out of a `TraceLog` have to change whenever
+//! the way traces are stored does. A `TraceVisitor` is instead handed each piece of a traced error
+//! in turn by `TracedError::visit` or `TracedReport::visit`: first its metadata as key-value
+//! pairs, then every frame of its trace, oldest first, each followed by its time and the values
+//! and backtrace captured for it, then the error itself, and finally each error in its `source`
+//! chain. Code that accepts either kind of traced error can take any `impl Visit`.
+//!
+//! ```rust
+//! use std::fmt;
+//! use std::num::ParseIntError;
+//! use trial_and_error::return_trace::visit::TraceVisitor;
+//! use trial_and_error::return_trace::{Ok, Result, TraceFrame, TracedError};
+//!
+//! #[derive(Default)]
+//! struct Lines(Vec<String>);
+//!
+//! impl TraceVisitor for Lines {
+//!     fn visit_frame(&mut self, _index: usize, frame: &TraceFrame) {
+//!         self.0.push(frame.to_string());
+//!     }
+//!
+//!     fn visit_metadata(&mut self, key: &str, value: &dyn fmt::Display) {
+//!         self.0.push(format!("{} = {}", key, value));
+//!     }
+//! }
+//!
+//! fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+//!     let value = input.parse::<u32>()?;
+//!
+//!     Ok(value)
+//! }
+//!
+//! let mut lines = Lines::default();
+//! parse("four").unwrap_err().visit(&mut lines);
+//!
+//! assert_eq!("message = invalid digit found in string", lines.0[0]);
+//! ```
+
+use std::any;
+use std::backtrace::Backtrace;
+use std::error::Error;
+use std::fmt;
+
+use super::age::HopTime;
+use super::capture::Capture;
+use super::fields::{self, Fields};
+use super::storage::TraceStorage;
+use super::{TraceFrame, TraceLog, TracedError, TracedReport};
+
+/// Receives the pieces of a traced error, in order.
+///
+/// Only `visit_frame` is required; metadata, the error, and its sources are ignored unless the
+/// visitor overrides the corresponding method.
+pub trait TraceVisitor {
+    /// Visit the `index`th frame of the trace, counting from the first location recorded.
+    fn visit_frame(&mut self, index: usize, frame: &TraceFrame);
+
+    /// Visit the time the `frame`th frame was recorded, right after that frame, if hop timing was
+    /// on; see the `age` module.
+    fn visit_hop_time(&mut self, _frame: usize, _time: &HopTime) {}
+
+    /// Visit a value captured with `capture!` for the `frame`th frame, after its time.
+    ///
+    /// Values captured for a frame that was never recorded are visited after the last frame.
+    fn visit_capture(&mut self, _frame: usize, _capture: &Capture) {}
+
+    /// Visit a backtrace captured with `deep_trace!` for the `frame`th frame, after the values
+    /// captured for it.
+    fn visit_backtrace(&mut self, _frame: usize, _backtrace: &Backtrace) {}
+
+    /// Visit a piece of metadata about the error, like its message or type.
+    fn visit_metadata(&mut self, _key: &str, _value: &dyn fmt::Display) {}
+
+    /// Visit the traced error itself, after its frames and before its `source` chain.
+    fn visit_error(&mut self, _error: &(dyn Error + 'static)) {}
+
+    /// Visit an error in the `source` chain, `depth` levels below the traced error.
+    fn visit_source(&mut self, _depth: usize, _source: &(dyn Error + 'static)) {}
+}
+
+/// A traced error that can be walked with a `TraceVisitor`.
+pub trait Visit {
+    /// Walk the error with `visitor`.
+    fn visit<V>(&self, visitor: &mut V)
+    where
+        V: TraceVisitor + ?Sized;
+}
+
+/// Hand the `fields` of an error to `visitor` as metadata, skipping those with reserved names.
+fn visit_fields<V>(visitor: &mut V, fields: &Fields)
+where
+    V: TraceVisitor + ?Sized,
+{
+    for (key, value) in fields.iter().filter(|(key, _)| !fields::is_reserved(key)) {
+        visitor.visit_metadata(key, value);
+    }
+}
+
+/// Hand `frames`, whatever `log` attached to them, and the source chain of `error` to `visitor`.
+fn walk<V>(
+    visitor: &mut V,
+    frames: &[TraceFrame],
+    log: Option<&TraceLog>,
+    error: &(dyn Error + 'static),
+) where
+    V: TraceVisitor + ?Sized,
+{
+    let visit_attached = |visitor: &mut V, index: usize| {
+        if let Some(log) = log {
+            for time in log.hop_times().filter(|time| time.frame() == index) {
+                visitor.visit_hop_time(index, time);
+            }
+            for capture in log.captures_of(index) {
+                visitor.visit_capture(index, capture);
+            }
+            if let Some(backtrace) = log.backtrace_of(index) {
+                visitor.visit_backtrace(index, backtrace);
+            }
+        }
+    };
+
+    for (index, frame) in frames.iter().enumerate() {
+        visitor.visit_frame(index, frame);
+        visit_attached(visitor, index);
+    }
+    visit_attached(visitor, frames.len());
+
+    visitor.visit_error(error);
+
+    let mut source = error.source();
+    let mut depth = 1;
+
+    while let Some(error) = source {
+        visitor.visit_source(depth, error);
+        source = error.source();
+        depth += 1;
+    }
+}
+
+impl<E, S> TracedError<E, S>
+where
+    E: Error + 'static,
+    S: TraceStorage,
+{
+    /// Walk the error with `visitor`.
+    ///
+    /// The metadata visited is the error's `message` and its `type`, followed by its
+    /// `traceparent` if it has one, and then its fields.
+    pub fn visit<V>(&self, visitor: &mut V)
+    where
+        V: TraceVisitor + ?Sized,
+    {
+        self.mark_handled();
+        visitor.visit_metadata("message", &self.error);
+        visitor.visit_metadata("type", &any::type_name::<E>());
+        if let Some(parent) = self.trace_parent() {
+            visitor.visit_metadata("traceparent", parent);
+        }
+        visit_fields(visitor, self.fields());
+        walk(
+            visitor,
+            self.trace.frames(),
+            self.trace.as_trace_log(),
+            &self.error,
+        );
+    }
+}
+
+impl<E, S> Visit for TracedError<E, S>
+where
+    E: Error + 'static,
+    S: TraceStorage,
+{
+    fn visit<V>(&self, visitor: &mut V)
+    where
+        V: TraceVisitor + ?Sized,
+    {
+        TracedError::visit(self, visitor);
+    }
+}
+
+impl TracedReport {
+    /// Walk the error with `visitor`.
+    ///
+    /// The type of a `TracedReport` is erased, so the only metadata visited is its `message`,
+    /// followed by its `traceparent` if it has one, and then its fields.
+    pub fn visit<V>(&self, visitor: &mut V)
+    where
+        V: TraceVisitor + ?Sized,
+    {
+        self.mark_handled();
+        visitor.visit_metadata("message", &self.error);
+        if let Some(parent) = self.trace_parent() {
+            visitor.visit_metadata("traceparent", parent);
+        }
+        visit_fields(visitor, self.fields());
+        walk(
+            visitor,
+            self.trace.frames(),
+            Some(&self.trace),
+            self.inner(),
+        );
+    }
+}
+
+impl Visit for TracedReport {
+    fn visit<V>(&self, visitor: &mut V)
+    where
+        V: TraceVisitor + ?Sized,
+    {
+        TracedReport::visit(self, visitor);
+    }
+}
+
+/// Visitor that collects everything needed to render an error once the walk is complete.
+#[derive(Default)]
+pub(crate) struct Collector {
+    pub(crate) message: String,
+    pub(crate) metadata: Vec<(String, String)>,
+    pub(crate) frames: Vec<TraceFrame>,
+    pub(crate) sources: Vec<String>,
+}
+
+impl Collector {
+    /// Walk `error`, collecting all of its pieces.
+    pub(crate) fn collect<E>(error: &E) -> Self
+    where
+        E: Visit + ?Sized,
+    {
+        let mut collector = Collector::default();
+        error.visit(&mut collector);
+        collector
+    }
+}
+
+impl TraceVisitor for Collector {
+    fn visit_frame(&mut self, _index: usize, frame: &TraceFrame) {
+        self.frames.push(*frame);
+    }
+
+    fn visit_metadata(&mut self, key: &str, value: &dyn fmt::Display) {
+        if key == "message" {
+            self.message = value.to_string();
+        } else {
+            self.metadata.push((key.to_string(), value.to_string()));
+        }
+    }
+
+    fn visit_source(&mut self, _depth: usize, source: &(dyn Error + 'static)) {
+        self.sources.push(source.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::return_trace::{Err, IntoTraced, Ok, Result};
+
+    #[derive(Debug)]
+    struct Outer(std::num::ParseIntError);
+
+    impl fmt::Display for Outer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("could not read config")
+        }
+    }
+
+    impl Error for Outer {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl TraceVisitor for Recorder {
+        fn visit_frame(&mut self, index: usize, frame: &TraceFrame) {
+            let line = frame.location().line();
+            self.0.push(format!("frame {} line {}", index, line));
+        }
+
+        fn visit_metadata(&mut self, key: &str, value: &dyn fmt::Display) {
+            self.0.push(format!("{}: {}", key, value));
+        }
+
+        fn visit_capture(&mut self, frame: usize, capture: &Capture) {
+            let (name, value) = (capture.name(), capture.value());
+            self.0
+                .push(format!("capture {} {} = {}", frame, name, value));
+        }
+
+        fn visit_source(&mut self, depth: usize, source: &(dyn Error + 'static)) {
+            self.0.push(format!("source {}: {}", depth, source));
+        }
+    }
+
+    fn read() -> Result<(), TracedError<Outer>> {
+        let error = "x".parse::<u32>().unwrap_err();
+        Err(Outer(error).into_traced())
+    }
+
+    fn load() -> Result<(), TracedError<Outer>> {
+        read()?;
+        Ok(())
+    }
+
+    #[test]
+    fn visits_metadata_then_frames_then_sources() {
+        let error = load().unwrap_err();
+        let frames = error.trace_log().frames();
+
+        let mut recorder = Recorder::default();
+        error.visit(&mut recorder);
+
+        assert_eq!(
+            vec![
+                "message: could not read config".to_string(),
+                format!("type: {}", any::type_name::<Outer>()),
+                format!("frame 0 line {}", frames[0].location().line()),
+                format!("frame 1 line {}", frames[1].location().line()),
+                "source 1: invalid digit found in string".to_string(),
+            ],
+            recorder.0
+        );
+    }
+
+    #[test]
+    fn report_visits_message_without_type() {
+        let report = TracedReport::new(load().unwrap_err());
+
+        let mut recorder = Recorder::default();
+        report.visit(&mut recorder);
+
+        assert_eq!("message: could not read config", recorder.0[0]);
+        assert!(recorder.0[1].starts_with("frame 0 "));
+        assert_eq!(3, report.trace_log().len());
+    }
+
+    #[test]
+    fn captures_follow_their_frame() {
+        let attempt = 2;
+        let error = crate::capture!(load().unwrap_err(), attempt);
+
+        let mut recorder = Recorder::default();
+        error.visit(&mut recorder);
+
+        assert!(recorder.0[3].starts_with("frame 1 "));
+        assert_eq!("capture 2 attempt = 2", recorder.0[4]);
+        assert!(recorder.0[5].starts_with("source 1: "));
+    }
+}
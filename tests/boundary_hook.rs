@@ -0,0 +1,61 @@
+//! Tests that register a boundary hook.
+//!
+//! The hook is a global setting, and every traced error leaving the traced world is handed to it,
+//! so these tests run in a process of their own, one at a time.
+
+#![feature(once_cell)]
+
+use std::error::Error;
+use std::lazy::SyncLazy;
+use std::num::ParseIntError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use trial_and_error::return_trace::{
+    set_boundary_hook, take_boundary_hook, Ok, Result, TracedError,
+};
+
+static SERIAL: SyncLazy<Mutex<()>> = SyncLazy::new(|| Mutex::new(()));
+
+/// Take the boundary hook for the duration of a test, with no hook registered.
+fn serial() -> MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    take_boundary_hook();
+    guard
+}
+
+fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = input.parse::<u32>()?;
+    Ok(value)
+}
+
+fn double(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = parse(input)?;
+    Ok(value * 2)
+}
+
+#[test]
+fn question_mark_into_std_result_flushes_trace_to_hook() {
+    static FLUSHED_FRAMES: AtomicUsize = AtomicUsize::new(0);
+
+    fn std_caller(input: &str) -> std::result::Result<u32, ParseIntError> {
+        let value = double(input).map_err(TracedError::into_inner)?;
+        std::result::Result::Ok(value)
+    }
+
+    fn std_caller_with_trace(input: &str) -> std::result::Result<u32, Box<dyn Error>> {
+        let value = double(input)?;
+        std::result::Result::Ok(value)
+    }
+
+    let _serial = serial();
+    set_boundary_hook(Box::new(|trace| {
+        FLUSHED_FRAMES.fetch_add(trace.len(), Ordering::SeqCst);
+    }));
+
+    assert!(std_caller("nope").is_err());
+    assert!(std_caller_with_trace("nope").is_err());
+    assert!(take_boundary_hook().is_some());
+
+    assert_eq!(3, FLUSHED_FRAMES.load(Ordering::SeqCst));
+}
@@ -0,0 +1,44 @@
+//! Tests that turn colored output on.
+//!
+//! Colors are a global setting, so these tests run in a process of their own, one at a time.
+
+#![feature(once_cell)]
+
+use std::lazy::SyncLazy;
+use std::num::ParseIntError;
+use std::sync::{Mutex, MutexGuard};
+
+use trial_and_error::return_trace::setup::set_colors;
+use trial_and_error::return_trace::{Ok, Result, TracedError, TracedReport};
+
+static SERIAL: SyncLazy<Mutex<()>> = SyncLazy::new(|| Mutex::new(()));
+
+/// Take the color setting for the duration of a test, with colors off.
+fn serial() -> MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    set_colors(false);
+    guard
+}
+
+fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = input.parse::<u32>()?;
+
+    Ok(value)
+}
+
+#[test]
+fn colors_apply_to_traced_reports() {
+    let _serial = serial();
+
+    let report = TracedReport::new(parse("four").unwrap_err());
+    let plain = format!("{:?}", report);
+    assert!(plain.contains("\n\nError return trace:\n   0: "));
+    assert!(!plain.contains('\x1b'));
+
+    set_colors(true);
+    let colored = format!("{:?}", report);
+    assert!(colored.contains("\n\n\x1b[1mError return trace:\x1b[0m\n\x1b[2m   0\x1b[0m: "));
+
+    set_colors(false);
+    assert_eq!(plain, format!("{:?}", report));
+}
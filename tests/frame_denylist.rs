@@ -0,0 +1,67 @@
+//! Tests that set the frame denylist and the trace mode.
+//!
+//! Both are global settings, so these tests run in a process of their own, one at a time.
+
+#![feature(once_cell)]
+
+mod generated;
+
+use std::lazy::SyncLazy;
+use std::num::ParseIntError;
+use std::sync::{Mutex, MutexGuard};
+
+use trial_and_error::return_trace::location::SourcePosition;
+use trial_and_error::return_trace::{
+    clear_frame_denylist, set_frame_denylist, set_trace_mode, IntoTraced, MissingValue, Ok, Result,
+    TraceMode, TracedError,
+};
+
+static SERIAL: SyncLazy<Mutex<()>> = SyncLazy::new(|| Mutex::new(()));
+
+/// Take the global settings for the duration of a test, with no denylist and full traces.
+fn serial() -> MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    clear_frame_denylist();
+    set_trace_mode(TraceMode::Full);
+    guard
+}
+
+fn load(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = generated::parse(input)?;
+
+    Ok(value)
+}
+
+#[test]
+fn question_marks_in_denylisted_files_are_skipped() {
+    let _serial = serial();
+
+    assert_eq!(2, load("four").unwrap_err().trace_log().len());
+
+    set_frame_denylist(vec!["*/generated/*"]);
+    let error = load("four").unwrap_err();
+    clear_frame_denylist();
+
+    let frames = error.trace_log().frames();
+    assert_eq!(1, frames.len());
+    assert!(frames[0].location().file().ends_with("frame_denylist.rs"));
+}
+
+#[test]
+fn positions_follow_the_denylist_and_trace_mode() {
+    let _serial = serial();
+
+    let mut error = MissingValue::of::<u32>().into_traced();
+    set_frame_denylist(vec!["*/generated/*"]);
+    error.record_at(&SourcePosition::new("templates/generated/page.html", 3, 1));
+    error.record_at(&SourcePosition::new("templates/page.html", 7, 1));
+    clear_frame_denylist();
+
+    set_trace_mode(TraceMode::Counters);
+    error.record_at(&SourcePosition::new("templates/page.html", 9, 1));
+    set_trace_mode(TraceMode::Full);
+
+    let frames = error.trace_log().frames();
+    assert_eq!(2, frames.len());
+    assert_eq!("templates/page.html:7:1", frames[1].to_string());
+}
@@ -0,0 +1,11 @@
+//! Stands in for generated code, whose frames the tests drop with the frame denylist.
+
+use std::num::ParseIntError;
+
+use trial_and_error::return_trace::{Ok, Result, TracedError};
+
+pub fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = input.parse::<u32>()?;
+
+    Ok(value)
+}
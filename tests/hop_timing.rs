@@ -0,0 +1,108 @@
+//! Tests that turn hop timing on.
+//!
+//! Timing is a global setting, so these tests run in a process of their own, one at a time, on a
+//! clock they advance themselves.
+
+#![feature(once_cell)]
+
+use std::lazy::SyncLazy;
+use std::num::ParseIntError;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use trial_and_error::return_trace::age::{set_clock, set_hop_timing, HopTime};
+use trial_and_error::return_trace::chrome_trace::ChromeTrace;
+use trial_and_error::return_trace::{Ok, Result, TraceLog, TracedError};
+
+static SERIAL: SyncLazy<Mutex<()>> = SyncLazy::new(|| Mutex::new(()));
+
+static START: SyncLazy<Instant> = SyncLazy::new(Instant::now);
+
+static ELAPSED_MS: AtomicU64 = AtomicU64::new(0);
+
+fn clock() -> Instant {
+    *START + Duration::from_millis(ELAPSED_MS.load(Ordering::Relaxed))
+}
+
+fn advance(ms: u64) {
+    ELAPSED_MS.fetch_add(ms, Ordering::Relaxed);
+}
+
+/// Take the timing settings for the duration of a test, with timing off and the clock at zero.
+fn serial() -> MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    set_hop_timing(false);
+    set_clock(clock);
+    ELAPSED_MS.store(0, Ordering::Relaxed);
+    guard
+}
+
+#[test]
+fn hops_are_timed_only_while_timing_is_on() {
+    let _serial = serial();
+
+    let mut trace = TraceLog::new();
+    trace.push(Location::caller());
+    assert_eq!(None, trace.age());
+
+    set_hop_timing(true);
+    trace.push(Location::caller());
+    trace.push(Location::caller());
+    set_hop_timing(false);
+
+    let frames: Vec<_> = trace.hop_times().map(HopTime::frame).collect();
+    assert_eq!(vec![1, 2], frames);
+    assert_eq!(None, trace.created());
+    assert_eq!(None, trace.age());
+}
+
+#[test]
+fn traces_timed_from_their_first_frame_age() {
+    let _serial = serial();
+
+    set_hop_timing(true);
+    let mut trace = TraceLog::new();
+    trace.push(Location::caller());
+    advance(5);
+    trace.push(Location::caller());
+    set_hop_timing(false);
+    advance(10);
+
+    assert_eq!(Some(*START), trace.created());
+    assert_eq!(Some(Duration::from_millis(15)), trace.age());
+}
+
+fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = input.parse::<u32>()?;
+
+    Ok(value)
+}
+
+/// Parse `input`, taking `ms` to get the result back up.
+fn slow_parse(input: &str, ms: u64) -> Result<u32, TracedError<ParseIntError>> {
+    let result = parse(input);
+    advance(ms);
+    let value = result?;
+
+    Ok(value)
+}
+
+#[test]
+fn hops_become_slices_until_the_next_hop() {
+    let _serial = serial();
+
+    set_hop_timing(true);
+    let error = slow_parse("four", 2).unwrap_err();
+    set_hop_timing(false);
+
+    let mut trace = ChromeTrace::new();
+    assert!(trace.add(&error));
+
+    let json = trace.to_json();
+    assert!(json.contains("\"args\":{\"name\":\"invalid digit found in string\"}}"));
+    assert!(json.contains("\"ts\":0.000,\"ph\":\"X\",\"dur\":2000.000"));
+    assert!(json.contains("\"ts\":2000.000,\"ph\":\"i\""));
+    assert!(json.ends_with("],\"displayTimeUnit\":\"ms\"}"));
+}
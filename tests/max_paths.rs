@@ -0,0 +1,38 @@
+//! Tests that cap the number of interned paths.
+//!
+//! The cap applies to the global path table, so these tests run in a process of their own.
+
+use std::panic::Location;
+
+use trial_and_error::return_trace::interner::{self, set_max_paths};
+use trial_and_error::return_trace::location::SourcePosition;
+use trial_and_error::return_trace::{CompactLocation, TraceFrame};
+
+fn file_of(path: &str) -> &'static str {
+    CompactLocation::of(&SourcePosition::new(path, 1, 1)).file()
+}
+
+#[test]
+fn paths_past_the_cap_are_unknown() {
+    let before = interner::stats();
+    set_max_paths(Some(before.paths + 2));
+
+    assert_eq!("scripts/first.lua", file_of("scripts/first.lua"));
+    assert_eq!("scripts/second.lua", file_of("scripts/second.lua"));
+    assert_eq!("<unknown>", file_of("scripts/third.lua"));
+
+    // Rust sources are turned away the same way
+    let frame = TraceFrame::new(Location::caller());
+    assert_eq!("<unknown>", frame.location().file());
+
+    let after = interner::stats();
+    assert_eq!(before.paths + 2, after.paths);
+    assert_eq!(before.overflowed + 2, after.overflowed);
+
+    // Paths that made it into the table still resolve
+    assert_eq!("scripts/first.lua", file_of("scripts/first.lua"));
+
+    set_max_paths(None);
+    assert_eq!("scripts/third.lua", file_of("scripts/third.lua"));
+    assert_eq!(before.overflowed + 2, interner::stats().overflowed);
+}
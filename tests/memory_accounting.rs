@@ -0,0 +1,53 @@
+//! Tests that check the global tally of the memory held by traces.
+//!
+//! Every trace recorded while accounting is on adds to the tally, so these tests run in a process
+//! of their own, where the tally only counts the traces they record.
+
+use std::panic::Location;
+
+use trial_and_error::return_trace::memory::{set_memory_accounting, trace_memory, TraceMemory};
+use trial_and_error::return_trace::TraceLog;
+
+#[test]
+fn accounted_traces_are_taken_back_when_dropped() {
+    set_memory_accounting(true);
+    assert_eq!(TraceMemory::default(), trace_memory());
+
+    let mut trace = TraceLog::new();
+    trace.push(Location::caller());
+    let usage = trace.memory_usage() as u64;
+    assert_eq!(
+        TraceMemory {
+            traces: 1,
+            bytes: usage
+        },
+        trace_memory()
+    );
+
+    let clone = trace.clone();
+    assert_eq!(
+        TraceMemory {
+            traces: 2,
+            bytes: 2 * usage
+        },
+        trace_memory()
+    );
+
+    drop(trace);
+    assert_eq!(
+        TraceMemory {
+            traces: 1,
+            bytes: usage
+        },
+        trace_memory()
+    );
+
+    drop(clone);
+    assert_eq!(TraceMemory::default(), trace_memory());
+
+    // Traces that grew while accounting was off aren't counted
+    set_memory_accounting(false);
+    let mut untracked = TraceLog::new();
+    untracked.push(Location::caller());
+    assert_eq!(TraceMemory::default(), trace_memory());
+}
@@ -0,0 +1,47 @@
+//! Tests that set the status mapper of error responses.
+//!
+//! The mapper is a global setting, so these tests run in a process of their own, one at a time.
+
+#![feature(once_cell)]
+
+use std::fmt;
+use std::lazy::SyncLazy;
+use std::sync::{Mutex, MutexGuard};
+
+use trial_and_error::return_trace::response::{
+    set_status_mapper, take_status_mapper, ErrorResponse,
+};
+use trial_and_error::return_trace::{IntoTraced, MissingValue, TracedError, TracedReport};
+
+static SERIAL: SyncLazy<Mutex<()>> = SyncLazy::new(|| Mutex::new(()));
+
+/// Take the status mapper for the duration of a test, with no mapper set.
+fn serial() -> MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    take_status_mapper();
+    guard
+}
+
+#[test]
+fn status_mapper_picks_status() {
+    let _serial = serial();
+
+    set_status_mapper(Box::new(|error| {
+        if error.is::<MissingValue>() || error.is::<TracedError<MissingValue>>() {
+            Some(404)
+        } else {
+            None
+        }
+    }));
+
+    let error = MissingValue::of::<u32>().into_traced();
+    let report = TracedReport::new(error.clone());
+    let unmapped = fmt::Error.into_traced();
+
+    assert_eq!(404, ErrorResponse::with_format(&error, None).status());
+    assert_eq!(404, ErrorResponse::with_format(&report, None).status());
+    assert_eq!(500, ErrorResponse::with_format(&unmapped, None).status());
+
+    assert!(take_status_mapper().is_some());
+    assert_eq!(500, ErrorResponse::with_format(&error, None).status());
+}
@@ -0,0 +1,88 @@
+//! Tests that detect traced errors dropped without being handled.
+//!
+//! Detection and the unhandled hook are global settings, so these tests run in a process of their
+//! own, one at a time, counting the warnings the hook is handed.
+
+#![feature(once_cell)]
+
+use std::lazy::SyncLazy;
+use std::num::ParseIntError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use trial_and_error::return_trace::sink::ErrorSink;
+use trial_and_error::return_trace::unhandled::{set_unhandled_detection, set_unhandled_hook};
+use trial_and_error::return_trace::visit::TraceVisitor;
+use trial_and_error::return_trace::{Ok, Result, TraceFrame, TracedError, TracedReport};
+
+static SERIAL: SyncLazy<Mutex<()>> = SyncLazy::new(|| Mutex::new(()));
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Take the detection settings for the duration of a test, with detection on and no warnings.
+fn serial() -> MutexGuard<'static, ()> {
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    set_unhandled_detection(true);
+    set_unhandled_hook(Box::new(|_trace| {
+        WARNINGS.fetch_add(1, Ordering::Relaxed);
+    }));
+    WARNINGS.store(0, Ordering::Relaxed);
+    guard
+}
+
+fn warnings() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+fn parse(input: &str) -> Result<u32, TracedError<ParseIntError>> {
+    let value = input.parse::<u32>()?;
+
+    Ok(value)
+}
+
+struct Frames(usize);
+
+impl TraceVisitor for Frames {
+    fn visit_frame(&mut self, _index: usize, _frame: &TraceFrame) {
+        self.0 += 1;
+    }
+}
+
+#[test]
+fn unhandled_reports_warn_once() {
+    let _serial = serial();
+
+    drop(TracedReport::new(parse("four").unwrap_err()));
+    assert_eq!(1, warnings());
+}
+
+#[test]
+fn marked_and_visited_reports_dont_warn() {
+    let _serial = serial();
+
+    let report = TracedReport::new(parse("four").unwrap_err());
+    report.mark_handled();
+    drop(report);
+
+    let report = TracedReport::new(parse("five").unwrap_err());
+    let mut frames = Frames(0);
+    report.visit(&mut frames);
+    drop(report);
+
+    assert_eq!(2, frames.0);
+    assert_eq!(0, warnings());
+}
+
+#[test]
+fn errors_reported_to_a_sink_dont_warn() {
+    let _serial = serial();
+
+    let mut sink = ErrorSink::new(1);
+    for input in &["four", "four", "four"] {
+        sink.report(parse(input).unwrap_err());
+    }
+    sink.report_erased(TracedReport::new(parse("five").unwrap_err()));
+    drop(sink);
+
+    assert_eq!(0, warnings());
+}